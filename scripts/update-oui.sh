@@ -0,0 +1,39 @@
+#!/bin/sh
+# Regenerates data/oui.gz, the vendor prefixes embedded in nbtscanner, from
+# the IEEE MA-L, MA-M and MA-S registries.
+set -e
+
+cd "$(dirname "$0")/.."
+{
+    echo "# IEEE MA-L, MA-M and MA-S registry snapshot taken $(date -u +%Y-%m-%d),"
+    echo "# in Wireshark manuf format. Regenerate with scripts/update-oui.sh."
+    for registry in oui/oui.txt oui28/mam.txt oui36/oui36.txt; do
+        curl -fsSL "https://standards-oui.ieee.org/$registry"
+    done |
+        tr -d '\r' |
+        awk -F'\t' '
+            # `70-B3-D5   (hex)` gives the OUI, the `(base 16)` line after it
+            # either the same OUI or, for MA-M and MA-S, the block within it
+            /\(hex\)/ {
+                split($1, oui, " ")
+                gsub("-", "", oui[1])
+            }
+            /\(base 16\)/ {
+                split($1, range, " ")
+                split(range[1], bounds, "-")
+                if (bounds[2] == "") {
+                    printf "%s:%s:%s\t%s\n", substr(oui[1], 1, 2), substr(oui[1], 3, 2), substr(oui[1], 5, 2), $NF
+                    next
+                }
+                # The block is as long as the digits its first and last
+                # addresses share
+                shared = 0
+                while (shared < 6 && substr(bounds[1], shared + 1, 1) == substr(bounds[2], shared + 1, 1))
+                    shared++
+                mac = oui[1] bounds[1]
+                printf "%s:%s:%s:%s:%s:%s/%d\t%s\n", substr(mac, 1, 2), substr(mac, 3, 2), substr(mac, 5, 2),
+                    substr(mac, 7, 2), substr(mac, 9, 2), substr(mac, 11, 2), 24 + 4 * shared, $NF
+            }' |
+        sort
+} | gzip -9n > data/oui.gz
+echo "Wrote $(gzip -dc data/oui.gz | grep -vc '^#') prefixes to data/oui.gz"
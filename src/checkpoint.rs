@@ -0,0 +1,260 @@
+use crate::hex::{from_hex, to_hex};
+use crate::ip_range::TargetSet;
+use crate::nbt_packet::NetBiosPacket;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::Duration;
+
+/// The progress of a scan, saved periodically so an interrupted scan can be
+/// picked up where it left off
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// How the targets were given when the scan was started
+    pub spec: String,
+    ranges: Vec<(Ipv4Addr, Ipv4Addr)>,
+    #[serde(default)]
+    hostnames: Vec<(Ipv4Addr, String)>,
+    pub seed: Option<u64>,
+    /// The options the scan was started with, which a resumed scan has to
+    /// share
+    #[serde(default)]
+    pub options: ScanOptions,
+    /// Position in the scan order of the first target that hasn't finished
+    pub next_position: u64,
+    responses: Vec<SavedResponse>,
+    /// The targets that were closed or couldn't be probed
+    #[serde(default)]
+    unanswered: Vec<SavedUnanswered>,
+}
+
+/// The options that change which targets a scan reports and how, apart
+/// from the output format
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanOptions {
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub users: bool,
+    #[serde(default)]
+    pub show_all: bool,
+    #[serde(default)]
+    pub only_silent: bool,
+    /// The filter, as the options that set it
+    #[serde(default)]
+    pub filter: Vec<String>,
+}
+
+impl fmt::Display for ScanOptions {
+    /// As they'd be given on the command line
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut options: Vec<String> = Vec::new();
+        if self.retries > 0 {
+            options.push(format!("--retries {}", self.retries));
+        }
+        if self.users {
+            options.push(String::from("--users"));
+        }
+        if self.show_all {
+            options.push(String::from("--show-all"));
+        }
+        if self.only_silent {
+            options.push(String::from("--only-silent"));
+        }
+        options.extend(self.filter.iter().cloned());
+        if options.is_empty() {
+            write!(f, "no options")
+        } else {
+            write!(f, "{}", options.join(" "))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedResponse {
+    ip: Ipv4Addr,
+    data: String,
+    #[serde(default)]
+    rtt_ms: f64,
+    #[serde(default = "one_attempt")]
+    attempts: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedUnanswered {
+    ip: Ipv4Addr,
+    state: HostState,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Checkpoints from before retries were added only ever tried once
+fn one_attempt() -> u32 {
+    1
+}
+
+impl Checkpoint {
+    pub(crate) fn new(
+        spec: &str,
+        targets: &TargetSet,
+        seed: Option<u64>,
+        options: ScanOptions,
+        next_position: u64,
+        results: &[ScanResult],
+        unanswered: &[Unanswered],
+    ) -> Checkpoint {
+        Checkpoint {
+            spec: String::from(spec),
+            ranges: targets.ranges(),
+            hostnames: targets
+                .hostnames()
+                .iter()
+                .map(|(ip, hostname)| (*ip, hostname.clone()))
+                .collect(),
+            seed,
+            options,
+            next_position,
+            responses: results
+                .iter()
+                .map(|result| SavedResponse {
+                    ip: result.ip,
+                    data: to_hex(&result.packet.data[..result.packet.length]),
+                    rtt_ms: result.rtt.as_secs_f64() * 1000.0,
+                    attempts: result.attempts,
+                })
+                .collect(),
+            unanswered: unanswered
+                .iter()
+                .map(|unanswered| SavedUnanswered {
+                    ip: unanswered.ip,
+                    state: unanswered.state,
+                    error: unanswered.error.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the checkpoint next to `path` first and then renames it, so an
+    /// interruption never leaves a half-written file behind
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_string(self)?;
+        let partial = path.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(&partial, path)
+    }
+
+    pub fn targets(&self) -> TargetSet {
+        let mut targets = TargetSet::new();
+        for &(first, last) in self.ranges.iter() {
+            targets.add_range(first, last);
+        }
+        for (ip, hostname) in self.hostnames.iter() {
+            targets.add_host(*ip, hostname);
+        }
+        targets
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_position >= self.targets().len()
+    }
+
+    pub(crate) fn results(&self) -> Vec<ScanResult> {
+        let targets = self.targets();
+        self.responses
+            .iter()
+            .filter_map(|response| {
+                let bytes = from_hex(&response.data)?;
+                let mut data = [0u8; 1024];
+                let length = bytes.len().min(data.len());
+                data[..length].copy_from_slice(&bytes[..length]);
+                let packet = NetBiosPacket::from(response.ip, data, length);
+                let mut result =
+                    ScanResult::new(packet, Duration::from_secs_f64(response.rtt_ms.max(0.0) / 1000.0));
+                result.hostname = targets.hostname(response.ip).map(String::from);
+                result.attempts = response.attempts;
+                Some(result)
+            })
+            .collect()
+    }
+
+    pub(crate) fn unanswered(&self) -> Vec<Unanswered> {
+        let targets = self.targets();
+        self.unanswered
+            .iter()
+            .map(|saved| Unanswered {
+                ip: saved.ip,
+                hostname: targets.hostname(saved.ip).map(String::from),
+                state: saved.state,
+                error: saved.error.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_targets_and_responses() {
+        let targets: TargetSet = "10.192.4.1/24".parse().unwrap();
+        let mut data = [0u8; 1024];
+        data[..4].copy_from_slice(&[0xA2, 0x48, 0x84, 0x00]);
+        let packet = NetBiosPacket::from(Ipv4Addr::new(10, 192, 4, 7), data, 4);
+        let results = vec![ScanResult::new(packet, Duration::from_millis(12))];
+        let unanswered = vec![
+            Unanswered {
+                ip: Ipv4Addr::new(10, 192, 4, 9),
+                hostname: None,
+                state: HostState::Closed,
+                error: None,
+            },
+            Unanswered {
+                ip: Ipv4Addr::new(10, 192, 4, 10),
+                hostname: None,
+                state: HostState::Error,
+                error: Some(String::from("sendto: No route to host")),
+            },
+        ];
+
+        let options = ScanOptions {
+            users: true,
+            filter: vec![String::from("--workgroup CORP")],
+            ..ScanOptions::default()
+        };
+        let checkpoint = Checkpoint::new(
+            "10.192.4.1/24",
+            &targets,
+            Some(3),
+            options.clone(),
+            120,
+            &results,
+            &unanswered,
+        );
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let restored: Checkpoint = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.next_position, 120);
+        assert_eq!(restored.options, options);
+        assert_eq!(restored.options.to_string(), "--users --workgroup CORP");
+        assert_eq!(restored.targets().len(), 254);
+        let restored_results = restored.results();
+        assert_eq!(restored_results[0].ip, Ipv4Addr::new(10, 192, 4, 7));
+        assert_eq!(restored_results[0].packet.data[..4], [0xA2, 0x48, 0x84, 0x00]);
+        assert_eq!(restored_results[0].rtt, Duration::from_millis(12));
+        let restored_unanswered = restored.unanswered();
+        assert_eq!(restored_unanswered.len(), 2);
+        assert_eq!(restored_unanswered[0].state, HostState::Closed);
+        assert_eq!(restored_unanswered[1].ip, Ipv4Addr::new(10, 192, 4, 10));
+        assert_eq!(restored_unanswered[1].error, unanswered[1].error);
+    }
+}
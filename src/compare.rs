@@ -0,0 +1,81 @@
+use crate::filter_args;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use nbtscanner::diff::{self, Host};
+use nbtscanner::inventory::Inventory;
+use std::path::Path;
+
+/// The `diff` subcommand, which reports what changed between two scans
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("diff")
+        .about("Reports the hosts that appeared, vanished, moved or changed between two scans")
+        .arg(Arg::with_name("OLD")
+            .help("The earlier scan: a file saved with --output-format json or ndjson, or a scan id with --db")
+            .required(true)
+        ).arg(Arg::with_name("NEW")
+            .help("The later scan, given the same way")
+            .required(true)
+        ).arg(Arg::with_name("db")
+            .long("db")
+            .value_name("FILE")
+            .help("Read scans given by id from this inventory")
+            .takes_value(true)
+        ).arg(Arg::with_name("output-format")
+            .short("o")
+            .long("output-format")
+            .value_name("FORMAT")
+            .help("How to print the changes: text or json")
+            .takes_value(true)
+            .possible_values(&["text", "json"])
+            .default_value("text")
+        ).args(&filter_args::args())
+}
+
+pub fn run(matches: &ArgMatches) {
+    let inventory = matches.value_of("db").map(|path| match Inventory::open(Path::new(path)) {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            println!("Couldn't open inventory {}: {}", path, e);
+            std::process::exit(-1)
+        }
+    });
+
+    let filter = filter_args::filter(matches);
+    let mut old = load(matches.value_of("OLD").unwrap(), inventory.as_ref());
+    let mut new = load(matches.value_of("NEW").unwrap(), inventory.as_ref());
+    old.retain(|host| filter.matches_host(host));
+    new.retain(|host| filter.matches_host(host));
+    let changes = diff::diff(&old, &new);
+
+    if matches.value_of("output-format") == Some("json") {
+        match serde_json::to_string_pretty(&changes) {
+            Ok(json) => println!("{}", json),
+            Err(e) => println!("Couldn't write the changes: {}", e),
+        }
+    } else if changes.is_empty() {
+        println!("No changes");
+    } else {
+        for change in changes {
+            println!("{}", change);
+        }
+    }
+}
+
+/// Loads a scan given as a scan id when there's an inventory, or else as a
+/// saved results file
+fn load(scan: &str, inventory: Option<&Inventory>) -> Vec<Host> {
+    let loaded = match (inventory, scan.parse::<i64>()) {
+        (Some(inventory), Ok(scan_id)) => inventory
+            .scan_results(scan_id)
+            .map(|results| results.iter().map(Host::from).collect())
+            .map_err(|e| e.to_string()),
+        _ => diff::read_results_file(Path::new(scan))
+            .map_err(|e| format!("Couldn't read scan results {}: {}", scan, e)),
+    };
+    match loaded {
+        Ok(hosts) => hosts,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(-1)
+        }
+    }
+}
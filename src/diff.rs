@@ -0,0 +1,300 @@
+use crate::nbt_packet::NameEntry;
+use crate::scan_result::ScanResult;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+/// What a scan learned about one host, enough to tell whether it changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Host {
+    pub ip: Ipv4Addr,
+    pub name: String,
+    #[serde(default)]
+    pub workgroup: Option<String>,
+    pub mac: String,
+    /// The name table, so hosts can be filtered. Read from saved results but
+    /// left out of changes to keep them short.
+    #[serde(default, skip_serializing, deserialize_with = "read_names")]
+    pub names: Vec<NameEntry>,
+}
+
+/// A name table entry as the JSON output formats write it
+#[derive(Deserialize)]
+struct SavedName {
+    name: String,
+    suffix: String,
+    #[serde(default)]
+    flags: u16,
+}
+
+fn read_names<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<NameEntry>, D::Error> {
+    let saved: Vec<SavedName> = Vec::deserialize(deserializer)?;
+    saved
+        .into_iter()
+        .map(|saved| {
+            let suffix = u8::from_str_radix(&saved.suffix, 16).map_err(serde::de::Error::custom)?;
+            Ok(NameEntry {
+                name: saved.name,
+                suffix,
+                flags: saved.flags,
+            })
+        })
+        .collect()
+}
+
+impl From<&ScanResult> for Host {
+    fn from(result: &ScanResult) -> Host {
+        Host {
+            ip: result.ip,
+            name: result.packet.name(),
+            workgroup: result.packet.workgroup(),
+            mac: result.packet.mac_address(),
+            names: result.packet.names(),
+        }
+    }
+}
+
+/// A difference between two scans
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// A host answered at an address that didn't answer before
+    Appeared { host: Host },
+    /// An address that answered before didn't answer
+    Vanished { host: Host },
+    /// The same address answered with a different name
+    Renamed { ip: Ipv4Addr, old: String, new: String },
+    /// A name answered from a different address
+    Moved { name: String, old: Ipv4Addr, new: Ipv4Addr },
+    /// The same address answered with a different MAC, which can mean
+    /// spoofing or a replaced network card. Named after the new name.
+    MacChanged { ip: Ipv4Addr, name: String, old: String, new: String },
+    WorkgroupChanged {
+        ip: Ipv4Addr,
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Appeared { host } => write!(
+                f,
+                "+ {:<16}{} appeared ({}, {})",
+                host.ip,
+                host.name,
+                host.workgroup.as_deref().unwrap_or("no workgroup"),
+                host.mac
+            ),
+            Change::Vanished { host } => write!(f, "- {:<16}{} vanished", host.ip, host.name),
+            Change::Renamed { ip, old, new } => write!(f, "~ {:<16}renamed {} -> {}", ip, old, new),
+            Change::Moved { name, old, new } => write!(f, "> {:<16}{} moved from {}", new, name, old),
+            Change::MacChanged { ip, name, old, new } => {
+                write!(f, "! {:<16}{} changed MAC {} -> {}", ip, name, old, new)
+            }
+            Change::WorkgroupChanged { ip, name, old, new } => write!(
+                f,
+                "~ {:<16}{} changed workgroup {} -> {}",
+                ip,
+                name,
+                old.as_deref().unwrap_or("(none)"),
+                new.as_deref().unwrap_or("(none)")
+            ),
+        }
+    }
+}
+
+/// Compares two scans. Hosts are matched by address; a name that shows up
+/// at a new address after leaving its old one is reported as a move rather
+/// than as one host vanishing and another appearing.
+pub fn diff(old: &[Host], new: &[Host]) -> Vec<Change> {
+    let old_by_ip: BTreeMap<Ipv4Addr, &Host> = old.iter().map(|host| (host.ip, host)).collect();
+    let new_by_ip: BTreeMap<Ipv4Addr, &Host> = new.iter().map(|host| (host.ip, host)).collect();
+
+    // Names that left an address, and names that arrived at one
+    let departed: BTreeMap<&str, Ipv4Addr> = old_by_ip
+        .values()
+        .filter(|host| new_by_ip.get(&host.ip).map(|now| now.name != host.name).unwrap_or(true))
+        .map(|host| (host.name.as_str(), host.ip))
+        .collect();
+    let arrived: BTreeMap<&str, Ipv4Addr> = new_by_ip
+        .values()
+        .filter(|host| old_by_ip.get(&host.ip).map(|then| then.name != host.name).unwrap_or(true))
+        .map(|host| (host.name.as_str(), host.ip))
+        .collect();
+
+    let mut changes = Vec::new();
+    for (ip, host) in old_by_ip.iter() {
+        if new_by_ip.contains_key(ip) || arrived.contains_key(host.name.as_str()) {
+            continue;
+        }
+        changes.push(Change::Vanished { host: (*host).clone() });
+    }
+
+    for (ip, host) in new_by_ip.iter() {
+        match old_by_ip.get(ip) {
+            None => match departed.get(host.name.as_str()) {
+                Some(&old_ip) => changes.push(Change::Moved {
+                    name: host.name.clone(),
+                    old: old_ip,
+                    new: *ip,
+                }),
+                None => changes.push(Change::Appeared { host: (*host).clone() }),
+            },
+            Some(then) => {
+                if then.name != host.name {
+                    changes.push(Change::Renamed {
+                        ip: *ip,
+                        old: then.name.clone(),
+                        new: host.name.clone(),
+                    });
+                    if let Some(&old_ip) = departed.get(host.name.as_str()) {
+                        if old_ip != *ip {
+                            changes.push(Change::Moved {
+                                name: host.name.clone(),
+                                old: old_ip,
+                                new: *ip,
+                            });
+                        }
+                    }
+                }
+                // A renamed host with a new MAC is most likely a different
+                // machine, which is worth knowing on its own
+                if then.mac != host.mac {
+                    changes.push(Change::MacChanged {
+                        ip: *ip,
+                        name: host.name.clone(),
+                        old: then.mac.clone(),
+                        new: host.mac.clone(),
+                    });
+                }
+                if then.workgroup != host.workgroup {
+                    changes.push(Change::WorkgroupChanged {
+                        ip: *ip,
+                        name: host.name.clone(),
+                        old: then.workgroup.clone(),
+                        new: host.workgroup.clone(),
+                    });
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// Reads the hosts saved with `--output-format json` or `ndjson`
+pub fn read_results_file(path: &Path) -> io::Result<Vec<Host>> {
+    let contents = fs::read_to_string(path)?;
+    read_results(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads the hosts from saved JSON or NDJSON results, skipping the targets
+/// that didn't answer, which `--show-all` adds without a name table
+fn read_results(contents: &str) -> Result<Vec<Host>, serde_json::Error> {
+    let records: Vec<serde_json::Value> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(contents)?
+    } else {
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+    records
+        .into_iter()
+        // Results saved before hosts had a state are all hosts that answered
+        .filter(|record| record.get("state").map_or(true, |state| state == "responding"))
+        .map(serde_json::from_value)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(ip: [u8; 4], name: &str, mac: &str) -> Host {
+        Host {
+            ip: Ipv4Addr::from(ip),
+            name: String::from(name),
+            workgroup: Some(String::from("SPICE")),
+            mac: String::from(mac),
+            names: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_every_kind_of_change() {
+        let old = vec![
+            host([10, 1, 2, 3], "FILESERVER", "00:11:22:33:44:55"),
+            host([10, 1, 2, 4], "PRINTER", "00:11:22:33:44:66"),
+            host([10, 1, 2, 5], "LAPTOP", "00:11:22:33:44:77"),
+            host([10, 1, 2, 6], "OLDBOX", "00:11:22:33:44:88"),
+            host([10, 1, 2, 8], "SCANNER", "00:11:22:33:44:BB"),
+        ];
+        let mut moved_workgroup = host([10, 1, 2, 4], "PRINTER", "00:11:22:33:44:66");
+        moved_workgroup.workgroup = None;
+        let new = vec![
+            host([10, 1, 2, 3], "FILESERVER", "00:11:22:33:44:99"),
+            moved_workgroup,
+            host([10, 1, 2, 9], "LAPTOP", "00:11:22:33:44:77"),
+            host([10, 1, 2, 6], "NEWBOX", "00:11:22:33:44:CC"),
+            host([10, 1, 2, 7], "GUEST", "00:11:22:33:44:AA"),
+        ];
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 7);
+        assert!(changes.contains(&Change::MacChanged {
+            ip: Ipv4Addr::new(10, 1, 2, 3),
+            name: String::from("FILESERVER"),
+            old: String::from("00:11:22:33:44:55"),
+            new: String::from("00:11:22:33:44:99"),
+        }));
+        assert!(changes.contains(&Change::Moved {
+            name: String::from("LAPTOP"),
+            old: Ipv4Addr::new(10, 1, 2, 5),
+            new: Ipv4Addr::new(10, 1, 2, 9),
+        }));
+        assert!(changes.contains(&Change::Renamed {
+            ip: Ipv4Addr::new(10, 1, 2, 6),
+            old: String::from("OLDBOX"),
+            new: String::from("NEWBOX"),
+        }));
+        assert!(changes.contains(&Change::MacChanged {
+            ip: Ipv4Addr::new(10, 1, 2, 6),
+            name: String::from("NEWBOX"),
+            old: String::from("00:11:22:33:44:88"),
+            new: String::from("00:11:22:33:44:CC"),
+        }));
+        assert!(changes.contains(&Change::Vanished {
+            host: host([10, 1, 2, 8], "SCANNER", "00:11:22:33:44:BB"),
+        }));
+        assert!(changes.iter().any(|change| matches!(change, Change::WorkgroupChanged { .. })));
+        assert!(changes.iter().any(|change| matches!(change, Change::Appeared { host } if host.name == "GUEST")));
+    }
+
+    #[test]
+    fn skips_targets_that_didnt_answer() {
+        let ndjson = r#"{"ip":"10.1.2.3","state":"responding","name":"FILESERVER","workgroup":"SPICE","mac":"00:11:22:33:44:55","names":[{"name":"FILESERVER","suffix":"20","group":false,"flags":1024}]}
+{"ip":"10.1.2.4","hostname":null,"state":"closed"}
+{"ip":"10.1.2.5","hostname":null,"state":"no_reply"}
+{"ip":"10.1.2.6","hostname":null,"state":"error","error":"No route to host"}
+"#;
+        let hosts = read_results(ndjson).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].names[0].suffix, 0x20);
+
+        let json = format!("[{}]", ndjson.trim().replace('\n', ","));
+        let hosts = read_results(&json).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert!(diff(&hosts, &read_results(ndjson).unwrap()).is_empty());
+
+        let without_state = r#"[{"ip":"10.1.2.3","name":"FILESERVER","mac":"00:11:22:33:44:55"}]"#;
+        assert_eq!(read_results(without_state).unwrap()[0].name, "FILESERVER");
+    }
+}
@@ -0,0 +1,175 @@
+use crate::diff::Host;
+use crate::nbt_packet::NameEntry;
+use crate::oui;
+use crate::roles::Role;
+use crate::scan_result::ScanResult;
+use regex::{Regex, RegexBuilder};
+
+/// Which hosts to keep. A host has to pass every filter that's set.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Matched against the computer name, ignoring case
+    pub name: Option<Regex>,
+    /// The workgroup or domain, ignoring case
+    pub workgroup: Option<String>,
+    /// Suffixes the host has to have registered, all of them
+    pub suffixes: Vec<u8>,
+    /// Part of the MAC vendor's name, ignoring case
+    pub vendor: Option<String>,
+    /// Roles the host has to have, any one of them
+    pub roles: Vec<Role>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    /// Compiles the pattern for `name`, which ignores case since NetBIOS
+    /// names are upper case
+    pub fn name_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+        RegexBuilder::new(pattern).case_insensitive(true).build()
+    }
+
+    /// Whether no filters are set, so every host passes
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.workgroup.is_none()
+            && self.suffixes.is_empty()
+            && self.vendor.is_none()
+            && self.roles.is_empty()
+    }
+
+    /// The command line options that set this filter, so it can be shown
+    /// and compared
+    pub fn options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        if let Some(ref pattern) = self.name {
+            options.push(format!("--name-match {}", pattern.as_str()));
+        }
+        if let Some(ref workgroup) = self.workgroup {
+            options.push(format!("--workgroup {}", workgroup));
+        }
+        for suffix in self.suffixes.iter() {
+            options.push(format!("--has-suffix {:02X}", suffix));
+        }
+        if let Some(ref vendor) = self.vendor {
+            options.push(format!("--vendor {}", vendor));
+        }
+        if !self.roles.is_empty() {
+            let roles: Vec<&str> = self.roles.iter().map(|role| role.name()).collect();
+            options.push(format!("--roles {}", roles.join(",")));
+        }
+        options
+    }
+
+    pub fn matches(&self, result: &ScanResult) -> bool {
+        self.is_empty()
+            || self.check(
+                &result.packet.name(),
+                result.packet.workgroup().as_deref(),
+                Some(result.packet.mac_bytes()),
+                &result.packet.names(),
+            )
+    }
+
+    /// The same for a host read back from saved results
+    pub fn matches_host(&self, host: &Host) -> bool {
+        self.is_empty()
+            || self.check(
+                &host.name,
+                host.workgroup.as_deref(),
+                parse_mac(&host.mac),
+                &host.names,
+            )
+    }
+
+    fn check(
+        &self,
+        name: &str,
+        workgroup: Option<&str>,
+        mac: Option<[u8; 6]>,
+        names: &[NameEntry],
+    ) -> bool {
+        if let Some(ref pattern) = self.name {
+            if !pattern.is_match(name) {
+                return false;
+            }
+        }
+        if let Some(ref wanted) = self.workgroup {
+            if !workgroup.is_some_and(|workgroup| workgroup.eq_ignore_ascii_case(wanted)) {
+                return false;
+            }
+        }
+        if !self
+            .suffixes
+            .iter()
+            .all(|suffix| names.iter().any(|entry| entry.suffix == *suffix))
+        {
+            return false;
+        }
+        if let Some(ref wanted) = self.vendor {
+            let vendor = mac.and_then(oui::describe).unwrap_or_default();
+            if !vendor.to_lowercase().contains(&wanted.to_lowercase()) {
+                return false;
+            }
+        }
+        self.roles.is_empty()
+            || Role::from_names(names)
+                .iter()
+                .any(|role| self.roles.contains(role))
+    }
+}
+
+/// Parses a MAC address written as six colon or dash separated hex bytes
+fn parse_mac(mac: &str) -> Option<[u8; 6]> {
+    let mut bytes = [0u8; 6];
+    let mut parts = mac.split([':', '-']);
+    for byte in bytes.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn result(names: &[(&[u8; 15], u8, u16)], mac: [u8; 6]) -> ScanResult {
+        let packet = packet(Ipv4Addr::new(10, 1, 2, 3), names, mac);
+        ScanResult::new(packet, Duration::from_millis(1))
+    }
+
+    #[test]
+    fn hosts_have_to_pass_every_filter() {
+        let vm = result(
+            &[
+                (b"WS-042         ", 0x00, 0x0400),
+                (b"WS-042         ", 0x20, 0x0400),
+                (b"CORP           ", 0x00, 0x8400),
+            ],
+            [0x00, 0x0C, 0x29, 0x12, 0x34, 0x56],
+        );
+        let mut filter = Filter::new();
+        assert!(filter.matches(&vm));
+
+        filter.name = Some(Filter::name_pattern("^ws-").unwrap());
+        filter.workgroup = Some(String::from("corp"));
+        filter.suffixes = vec![0x20];
+        filter.vendor = Some(String::from("vmware"));
+        assert!(filter.matches(&vm));
+        assert!(filter.matches_host(&Host::from(&vm)));
+
+        filter.suffixes.push(0x1C);
+        assert!(!filter.matches(&vm));
+        filter.suffixes.pop();
+        filter.roles = vec![Role::DomainController];
+        assert!(!filter.matches_host(&Host::from(&vm)));
+    }
+}
@@ -0,0 +1,67 @@
+use clap::{Arg, ArgMatches};
+use nbtscanner::filter::Filter;
+use nbtscanner::roles::Role;
+
+/// The options that pick which hosts are shown, shared by scans and `diff`
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("name-match")
+            .long("name-match")
+            .value_name("REGEX")
+            .help("Only show hosts whose computer name matches REGEX, ignoring case")
+            .takes_value(true),
+        Arg::with_name("workgroup")
+            .long("workgroup")
+            .value_name("NAME")
+            .help("Only show hosts in this workgroup or domain")
+            .takes_value(true),
+        Arg::with_name("has-suffix")
+            .long("has-suffix")
+            .value_name("XX")
+            .help("Only show hosts that registered a name with this hex suffix, e.g. 20 for file servers. \
+                  Can be given more than once")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+        Arg::with_name("vendor")
+            .long("vendor")
+            .value_name("TEXT")
+            .help("Only show hosts whose MAC vendor contains TEXT, ignoring case")
+            .takes_value(true),
+        Arg::with_name("roles")
+            .long("roles")
+            .value_name("LIST")
+            .help("Only show hosts with one of these roles: dc (domain controller), dmb (domain master \
+                  browser), lmb (local master browser), mb (either master browser) or fs (file server)")
+            .takes_value(true),
+    ]
+}
+
+/// The filter the options ask for, exiting if one of them is invalid
+pub fn filter(matches: &ArgMatches) -> Filter {
+    let mut filter = Filter::new();
+    if let Some(pattern) = matches.value_of("name-match") {
+        filter.name = Some(Filter::name_pattern(pattern).unwrap_or_else(|e| {
+            println!("Invalid --name-match pattern: {}", e);
+            std::process::exit(-1)
+        }));
+    }
+    filter.workgroup = matches.value_of("workgroup").map(String::from);
+    for suffix in matches.values_of("has-suffix").into_iter().flatten() {
+        match u8::from_str_radix(suffix.trim_start_matches("0x"), 16) {
+            Ok(suffix) => filter.suffixes.push(suffix),
+            Err(_) => {
+                println!("'{}' isn't a hex name suffix", suffix);
+                std::process::exit(-1)
+            }
+        }
+    }
+    filter.vendor = matches.value_of("vendor").map(String::from);
+    if let Some(roles) = matches.value_of("roles") {
+        filter.roles = Role::parse_list(roles).unwrap_or_else(|e| {
+            println!("{}", e);
+            std::process::exit(-1)
+        });
+    }
+    filter
+}
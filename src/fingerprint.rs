@@ -0,0 +1,354 @@
+use self::FingerprintError::*;
+use crate::oui::{self, MacKind};
+use crate::scan_result::ScanResult;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The rules built into the binary. See the file for the rule syntax.
+const EMBEDDED: &str = include_str!("../data/fingerprints.rules");
+
+static RULES: OnceLock<Rules> = OnceLock::new();
+
+/// A guess at what kind of device a host is, from its node status reply
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fingerprint {
+    pub class: String,
+    pub os: String,
+    /// 0-100
+    pub confidence: u8,
+    /// The name of the rule that matched
+    pub rule: String,
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} {} ({}%)", self.os, self.class, self.confidence)
+    }
+}
+
+#[derive(Debug)]
+pub enum FingerprintError {
+    ReadError(io::Error),
+    /// A line number and what's wrong with it
+    ParseError(usize, String),
+}
+
+impl Error for FingerprintError {}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ReadError(e) => write!(f, "{}", e),
+            ParseError(line, message) => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Suffix(u8),
+    Unique(u8),
+    Group(u8),
+    Name(String),
+    NamePrefix(String),
+    ZeroMac,
+    LocalMac,
+    Vendor(String),
+    ZeroStats,
+    NoStats,
+    NamesAtMost(usize),
+}
+
+impl Condition {
+    fn parse(condition: &str) -> Result<Condition, String> {
+        let (keyword, argument) = match condition.find(' ') {
+            Some(index) => (&condition[..index], condition[index + 1..].trim()),
+            None => (condition, ""),
+        };
+        let suffix = || {
+            u8::from_str_radix(argument, 16)
+                .map_err(|_| format!("'{}' isn't a hex suffix", argument))
+        };
+        let text = || {
+            if argument.is_empty() {
+                Err(format!("'{}' needs a value", keyword))
+            } else {
+                Ok(String::from(argument))
+            }
+        };
+        match keyword {
+            "suffix" => suffix().map(Condition::Suffix),
+            "unique" => suffix().map(Condition::Unique),
+            "group" => suffix().map(Condition::Group),
+            "name" => text().map(|name| Condition::Name(name.to_uppercase())),
+            "name-prefix" => text().map(|prefix| Condition::NamePrefix(prefix.to_uppercase())),
+            "zero-mac" => Ok(Condition::ZeroMac),
+            "local-mac" => Ok(Condition::LocalMac),
+            "vendor" => text().map(|vendor| Condition::Vendor(vendor.to_lowercase())),
+            "zero-stats" => Ok(Condition::ZeroStats),
+            "no-stats" => Ok(Condition::NoStats),
+            "names-at-most" => argument
+                .parse()
+                .map(Condition::NamesAtMost)
+                .map_err(|_| format!("'{}' isn't a number", argument)),
+            _ => Err(format!("unknown condition '{}'", keyword)),
+        }
+    }
+
+    fn holds(&self, result: &ScanResult) -> bool {
+        let names = result.packet.names();
+        let has = |suffix: u8, group: Option<bool>| {
+            names.iter().any(|entry| {
+                entry.suffix == suffix && (group.is_none() || group == Some(entry.is_group()))
+            })
+        };
+        match self {
+            Condition::Suffix(suffix) => has(*suffix, None),
+            Condition::Unique(suffix) => has(*suffix, Some(false)),
+            Condition::Group(suffix) => has(*suffix, Some(true)),
+            // __MSBROWSE__ is sent wrapped in control characters
+            Condition::Name(name) => names.iter().any(|entry| {
+                entry
+                    .name
+                    .trim_matches(|c: char| c.is_control() || c == ' ')
+                    .to_uppercase()
+                    == *name
+            }),
+            Condition::NamePrefix(prefix) => result
+                .packet
+                .name()
+                .to_uppercase()
+                .starts_with(prefix.as_str()),
+            Condition::ZeroMac => result.mac_kind() == MacKind::Zero,
+            Condition::LocalMac => result.mac_kind() == MacKind::LocallyAdministered,
+            Condition::Vendor(vendor) => {
+                result.mac_kind() == MacKind::Universal
+                    && oui::describe(result.packet.mac_bytes())
+                        .is_some_and(|name| name.to_lowercase().contains(vendor.as_str()))
+            }
+            Condition::ZeroStats => {
+                let statistics = result.packet.statistics();
+                !statistics.is_empty() && statistics.iter().all(|byte| *byte == 0)
+            }
+            Condition::NoStats => result.packet.statistics().is_empty(),
+            Condition::NamesAtMost(count) => names.len() <= *count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    name: String,
+    class: String,
+    os: String,
+    confidence: u8,
+    /// Each condition and whether it's negated
+    conditions: Vec<(bool, Condition)>,
+}
+
+impl Rule {
+    fn matches(&self, result: &ScanResult) -> bool {
+        self.conditions
+            .iter()
+            .all(|(negated, condition)| condition.holds(result) != *negated)
+    }
+}
+
+/// A set of fingerprint rules, read from files in the format of
+/// `data/fingerprints.rules`
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// The rules built into the binary
+    pub fn embedded() -> Rules {
+        Rules::parse(EMBEDDED).unwrap_or_default()
+    }
+
+    pub fn load(path: &Path) -> Result<Rules, FingerprintError> {
+        Rules::parse(&fs::read_to_string(path).map_err(ReadError)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Rules, FingerprintError> {
+        let mut rules: Vec<Rule> = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                check_complete(rules.last(), line_number)?;
+                rules.push(Rule {
+                    name: String::from(line[1..line.len() - 1].trim()),
+                    class: String::new(),
+                    os: String::new(),
+                    confidence: 0,
+                    conditions: Vec::new(),
+                });
+                continue;
+            }
+            let error = |message: String| ParseError(line_number, message);
+            let rule = rules
+                .last_mut()
+                .ok_or_else(|| error(String::from("expected a [rule] line first")))?;
+            let (key, value) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(error(String::from("expected `key = value`"))),
+            };
+            match key {
+                "class" => rule.class = String::from(value),
+                "os" => rule.os = String::from(value),
+                "confidence" => {
+                    rule.confidence = match value.parse() {
+                        Ok(confidence) if confidence <= 100 => confidence,
+                        _ => {
+                            return Err(error(format!(
+                                "confidence '{}' isn't between 0 and 100",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "match" => {
+                    for condition in value.split(',').map(str::trim) {
+                        let (negated, condition) = match condition.strip_prefix("not ") {
+                            Some(condition) => (true, condition.trim()),
+                            None => (false, condition),
+                        };
+                        rule.conditions
+                            .push((negated, Condition::parse(condition).map_err(error)?));
+                    }
+                }
+                _ => return Err(error(format!("unknown key '{}'", key))),
+            }
+        }
+        check_complete(rules.last(), contents.lines().count())?;
+        Ok(Rules { rules })
+    }
+
+    /// Puts `other`'s rules ahead of these, so they win ties
+    pub fn prepend(&mut self, mut other: Rules) {
+        other.rules.append(&mut self.rules);
+        self.rules = other.rules;
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The most confident matching rule's guess
+    pub fn fingerprint(&self, result: &ScanResult) -> Option<Fingerprint> {
+        let mut best: Option<&Rule> = None;
+        for rule in self.rules.iter().filter(|rule| rule.matches(result)) {
+            if !matches!(best, Some(best) if rule.confidence <= best.confidence) {
+                best = Some(rule);
+            }
+        }
+        best.map(|rule| Fingerprint {
+            class: rule.class.clone(),
+            os: rule.os.clone(),
+            confidence: rule.confidence,
+            rule: rule.name.clone(),
+        })
+    }
+}
+
+/// Rules without a class or any conditions are almost certainly mistakes
+fn check_complete(rule: Option<&Rule>, line_number: usize) -> Result<(), FingerprintError> {
+    match rule {
+        Some(rule) if rule.class.is_empty() || rule.conditions.is_empty() => Err(ParseError(
+            line_number,
+            format!("rule [{}] needs a class and a match line", rule.name),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Replaces the rules used by `fingerprint` with `rules`. Only takes effect
+/// before the first lookup.
+pub fn use_rules(rules: Rules) -> bool {
+    RULES.set(rules).is_ok()
+}
+
+/// What kind of device the host probably is
+pub fn fingerprint(result: &ScanResult) -> Option<Fingerprint> {
+    RULES.get_or_init(Rules::embedded).fingerprint(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    /// A reply with the given names, MAC and statistics block
+    fn result(names: &[(&[u8; 15], u8, u16)], mac: [u8; 6], statistics: usize) -> ScanResult {
+        let mut packet = packet(Ipv4Addr::new(10, 1, 2, 3), names, mac);
+        packet.length += statistics;
+        ScanResult::new(packet, Duration::from_millis(1))
+    }
+
+    #[test]
+    fn embedded_rules_tell_windows_samba_and_printers_apart() {
+        let rules = Rules::parse(EMBEDDED).unwrap();
+        let windows = result(
+            &[
+                (b"JACKIEG-WS     ", 0x20, 0x4400),
+                (b"JACKIEG-WS     ", 0x00, 0x4400),
+                (b"SPICE          ", 0x00, 0xC400),
+                (b"SPICE          ", 0x1E, 0xC400),
+            ],
+            [0x2C, 0x41, 0x38, 0xBA, 0xC3, 0x64],
+            46,
+        );
+        let samba = result(
+            &[
+                (b"FILES          ", 0x20, 0x0400),
+                (b"SPICE          ", 0x00, 0x8400),
+            ],
+            [0; 6],
+            46,
+        );
+        let printer = result(
+            &[(b"NPI3F2A1C      ", 0x00, 0x0400)],
+            [0x00, 0x17, 0xA4, 1, 2, 3],
+            0,
+        );
+
+        let fingerprint = rules.fingerprint(&windows).unwrap();
+        assert_eq!(
+            (fingerprint.os.as_str(), fingerprint.class.as_str()),
+            ("Windows", "workstation")
+        );
+        assert_eq!(rules.fingerprint(&samba).unwrap().rule, "samba-file-server");
+        assert_eq!(rules.fingerprint(&printer).unwrap().rule, "hp-jetdirect");
+    }
+
+    #[test]
+    fn added_rules_win_ties_and_bad_rules_are_reported() {
+        let mut rules = Rules::parse(EMBEDDED).unwrap();
+        let custom = "[lab-nas]\nclass = nas\nos = TrueNAS\nconfidence = 60\nmatch = zero-mac, name-prefix LAB\n";
+        rules.prepend(Rules::parse(custom).unwrap());
+        let nas = result(&[(b"LABSTORE       ", 0x00, 0x0400)], [0; 6], 46);
+        assert_eq!(rules.fingerprint(&nas).unwrap().os, "TrueNAS");
+        let lowercase = Rules::parse("[lab-store]\nclass = nas\nmatch = name labstore\n").unwrap();
+        assert_eq!(lowercase.fingerprint(&nas).unwrap().rule, "lab-store");
+
+        let error = Rules::parse("[broken]\nclass = nas\nmatch = has-fan\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: unknown condition 'has-fan'");
+        assert!(Rules::parse("[empty]\nclass = nas\n").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use self::InterfaceError::*;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::Ipv4Addr;
+
+/// An IPv4 subnet the host is directly connected to
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalSubnet {
+    pub interface: String,
+    pub address: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl fmt::Display for LocalSubnet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} ({})", self.address, self.prefix, self.interface)
+    }
+}
+
+#[derive(Debug)]
+pub enum InterfaceError {
+    EnumerationError(io::Error),
+    UnknownInterface(String),
+    NoSubnetsError,
+}
+
+impl Error for InterfaceError {}
+
+impl fmt::Display for InterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            EnumerationError(e) => write!(f, "Couldn't list the network interfaces: {}", e),
+            UnknownInterface(name) => {
+                write!(f, "No IPv4 interface named '{}' was found", name)
+            }
+            NoSubnetsError => write!(f, "No scannable IPv4 subnets were found on this host"),
+        }
+    }
+}
+
+/// Lists the subnets of every IPv4 interface that is up, optionally restricted
+/// to a single interface name. Loopback and link-local addresses are skipped.
+pub fn local_subnets(interface: Option<&str>) -> Result<Vec<LocalSubnet>, InterfaceError> {
+    let all = ipv4_interfaces().map_err(EnumerationError)?;
+
+    if let Some(name) = interface {
+        if !all.iter().any(|subnet| subnet.interface == name) {
+            return Err(UnknownInterface(String::from(name)));
+        }
+    }
+
+    let subnets: Vec<LocalSubnet> = all
+        .into_iter()
+        .filter(|subnet| interface.is_none() || interface == Some(subnet.interface.as_str()))
+        .filter(|subnet| is_scannable(subnet.address))
+        .collect();
+
+    if subnets.is_empty() {
+        return Err(NoSubnetsError);
+    }
+    Ok(subnets)
+}
+
+fn is_scannable(address: Ipv4Addr) -> bool {
+    !(address.is_loopback() || address.is_link_local() || address.is_unspecified())
+}
+
+fn prefix_from_netmask(netmask: Ipv4Addr) -> u8 {
+    u32::from(netmask).count_ones() as u8
+}
+
+#[cfg(unix)]
+fn ipv4_interfaces() -> io::Result<Vec<LocalSubnet>> {
+    use std::ffi::CStr;
+
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut subnets = Vec::new();
+    let mut cursor = addrs;
+    while !cursor.is_null() {
+        // SAFETY: `cursor` walks the list returned by getifaddrs, which stays
+        // valid until freeifaddrs is called below
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+
+        let is_up = entry.ifa_flags & libc::IFF_UP as libc::c_uint != 0;
+        let is_loopback = entry.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint != 0;
+        if !is_up || is_loopback || entry.ifa_addr.is_null() || entry.ifa_netmask.is_null() {
+            continue;
+        }
+        if i32::from(unsafe { (*entry.ifa_addr).sa_family }) != libc::AF_INET {
+            continue;
+        }
+
+        let address = unsafe { sockaddr_to_ipv4(entry.ifa_addr) };
+        let netmask = unsafe { sockaddr_to_ipv4(entry.ifa_netmask) };
+        let interface = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+
+        subnets.push(LocalSubnet {
+            interface,
+            address,
+            prefix: prefix_from_netmask(netmask),
+        });
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(subnets)
+}
+
+#[cfg(unix)]
+unsafe fn sockaddr_to_ipv4(addr: *const libc::sockaddr) -> Ipv4Addr {
+    let addr = &*(addr as *const libc::sockaddr_in);
+    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))
+}
+
+#[cfg(not(unix))]
+fn ipv4_interfaces() -> io::Result<Vec<LocalSubnet>> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "interface enumeration is only supported on Unix platforms",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_netmask_to_prefix() {
+        assert_eq!(prefix_from_netmask(Ipv4Addr::new(255, 255, 255, 0)), 24);
+        assert_eq!(prefix_from_netmask(Ipv4Addr::new(255, 254, 0, 0)), 15);
+    }
+
+    #[test]
+    fn skips_loopback_and_link_local_addresses() {
+        assert!(!is_scannable(Ipv4Addr::new(127, 0, 0, 1)));
+        assert!(!is_scannable(Ipv4Addr::new(169, 254, 10, 4)));
+        assert!(is_scannable(Ipv4Addr::new(10, 10, 48, 12)));
+    }
+}
@@ -0,0 +1,452 @@
+use self::InventoryError::*;
+use crate::nbt_packet::NetBiosPacket;
+use crate::output::ScanInfo;
+use crate::scan_result::ScanResult;
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use std::error::Error;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scans (
+        id INTEGER PRIMARY KEY,
+        started INTEGER NOT NULL,
+        finished INTEGER NOT NULL,
+        command_line TEXT NOT NULL,
+        targets INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS observations (
+        id INTEGER PRIMARY KEY,
+        scan_id INTEGER NOT NULL REFERENCES scans(id),
+        seen INTEGER NOT NULL,
+        ip TEXT NOT NULL,
+        hostname TEXT,
+        name TEXT NOT NULL,
+        workgroup TEXT,
+        mac TEXT NOT NULL,
+        rtt_ms REAL NOT NULL,
+        packet BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS observations_ip ON observations(ip);
+    CREATE INDEX IF NOT EXISTS observations_mac ON observations(mac);
+    CREATE TABLE IF NOT EXISTS observation_names (
+        observation_id INTEGER NOT NULL REFERENCES observations(id),
+        name TEXT NOT NULL,
+        suffix INTEGER NOT NULL,
+        flags INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS macs (
+        mac TEXT PRIMARY KEY,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL,
+        last_ip TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS names (
+        name TEXT NOT NULL,
+        suffix INTEGER NOT NULL,
+        first_seen INTEGER NOT NULL,
+        last_seen INTEGER NOT NULL,
+        last_ip TEXT NOT NULL,
+        PRIMARY KEY (name, suffix)
+    );
+";
+
+/// A record of every scan and host observation, kept in a SQLite database
+/// so hosts can be tracked across scans
+pub struct Inventory {
+    connection: Connection,
+}
+
+/// A scan recorded in the inventory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRecord {
+    pub id: i64,
+    pub started: u64,
+    pub finished: u64,
+    pub command_line: String,
+    pub targets: u64,
+    /// How many hosts answered
+    pub hosts: u64,
+}
+
+/// A host as it answered in one scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub scan_id: i64,
+    pub seen: u64,
+    pub ip: Ipv4Addr,
+    pub name: String,
+    pub workgroup: Option<String>,
+    pub mac: String,
+}
+
+/// A host (a NetBIOS name and MAC address) and when it was first and last
+/// seen, at the address it last answered from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sighting {
+    pub name: String,
+    pub mac: String,
+    pub ip: Ipv4Addr,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// How many scans it answered in
+    pub times_seen: u64,
+}
+
+/// When a MAC address or a name was first and last seen, and the address
+/// it last answered from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seen {
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub last_ip: Ipv4Addr,
+}
+
+#[derive(Debug)]
+pub enum InventoryError {
+    DatabaseError(rusqlite::Error),
+    UnknownScanError(i64),
+}
+
+impl Error for InventoryError {}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            DatabaseError(e) => write!(f, "Inventory database error: {}", e),
+            UnknownScanError(id) => write!(f, "There's no scan {} in the inventory", id),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for InventoryError {
+    fn from(e: rusqlite::Error) -> InventoryError {
+        DatabaseError(e)
+    }
+}
+
+impl Inventory {
+    /// Opens the inventory at `path`, creating it if it doesn't exist yet
+    pub fn open(path: &Path) -> Result<Inventory, InventoryError> {
+        Inventory::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Inventory, InventoryError> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Inventory { connection })
+    }
+
+    /// Records a finished scan and every host that answered it, returning
+    /// the scan's id
+    pub fn record_scan(&mut self, info: &ScanInfo, results: &[ScanResult]) -> Result<i64, InventoryError> {
+        let started = unix_seconds(info.started);
+        let seen = unix_seconds(info.finished);
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO scans (started, finished, command_line, targets) VALUES (?1, ?2, ?3, ?4)",
+            params![started as i64, seen as i64, info.command_line, info.targets as i64],
+        )?;
+        let scan_id = transaction.last_insert_rowid();
+
+        for result in results {
+            let ip = result.ip.to_string();
+            let mac = result.packet.mac_address();
+            transaction.execute(
+                "INSERT INTO observations
+                     (scan_id, seen, ip, hostname, name, workgroup, mac, rtt_ms, packet)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    scan_id,
+                    seen as i64,
+                    ip,
+                    result.hostname,
+                    result.packet.name(),
+                    result.packet.workgroup(),
+                    mac,
+                    result.rtt.as_secs_f64() * 1000.0,
+                    &result.packet.data[..result.packet.length],
+                ],
+            )?;
+            let observation_id = transaction.last_insert_rowid();
+
+            transaction.execute(
+                "INSERT INTO macs (mac, first_seen, last_seen, last_ip) VALUES (?1, ?2, ?2, ?3)
+                 ON CONFLICT (mac) DO UPDATE SET last_seen = excluded.last_seen, last_ip = excluded.last_ip",
+                params![mac, seen as i64, ip],
+            )?;
+            for entry in result.packet.names() {
+                transaction.execute(
+                    "INSERT INTO observation_names (observation_id, name, suffix, flags)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    params![observation_id, entry.name, entry.suffix, entry.flags],
+                )?;
+                transaction.execute(
+                    "INSERT INTO names (name, suffix, first_seen, last_seen, last_ip)
+                     VALUES (?1, ?2, ?3, ?3, ?4)
+                     ON CONFLICT (name, suffix) DO UPDATE
+                         SET last_seen = excluded.last_seen, last_ip = excluded.last_ip",
+                    params![entry.name, entry.suffix, seen as i64, ip],
+                )?;
+            }
+        }
+
+        transaction.commit()?;
+        Ok(scan_id)
+    }
+
+    /// Every recorded scan, oldest first
+    pub fn scans(&self) -> Result<Vec<ScanRecord>, InventoryError> {
+        let mut statement = self.connection.prepare(
+            "SELECT s.id, s.started, s.finished, s.command_line, s.targets,
+                    (SELECT count(*) FROM observations o WHERE o.scan_id = s.id)
+             FROM scans s ORDER BY s.id",
+        )?;
+        let scans = statement
+            .query_map([], |row| {
+                Ok(ScanRecord {
+                    id: row.get(0)?,
+                    started: row.get::<_, i64>(1)? as u64,
+                    finished: row.get::<_, i64>(2)? as u64,
+                    command_line: row.get(3)?,
+                    targets: row.get::<_, i64>(4)? as u64,
+                    hosts: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<ScanRecord>, rusqlite::Error>>()?;
+        Ok(scans)
+    }
+
+    /// The hosts that answered a recorded scan, rebuilt from their responses
+    pub fn scan_results(&self, scan_id: i64) -> Result<Vec<ScanResult>, InventoryError> {
+        let exists = self
+            .connection
+            .query_row("SELECT id FROM scans WHERE id = ?1", [scan_id], |row| row.get::<_, i64>(0))
+            .optional()?;
+        if exists.is_none() {
+            return Err(UnknownScanError(scan_id));
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT ip, hostname, rtt_ms, packet FROM observations WHERE scan_id = ?1 ORDER BY id",
+        )?;
+        let rows = statement
+            .query_map([scan_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, Vec<u8>>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+        let results = rows
+            .into_iter()
+            .filter_map(|(ip, hostname, rtt_ms, bytes)| {
+                let ip = ip.parse().ok()?;
+                let mut data = [0u8; 1024];
+                let length = bytes.len().min(data.len());
+                data[..length].copy_from_slice(&bytes[..length]);
+                let packet = NetBiosPacket::from(ip, data, length);
+                let mut result = ScanResult::new(packet, Duration::from_secs_f64(rtt_ms.max(0.0) / 1000.0));
+                result.hostname = hostname;
+                Some(result)
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Every time `ip` answered, oldest first
+    pub fn ip_history(&self, ip: Ipv4Addr) -> Result<Vec<Observation>, InventoryError> {
+        let mut statement = self.connection.prepare(
+            "SELECT scan_id, seen, ip, name, workgroup, mac FROM observations
+             WHERE ip = ?1 ORDER BY seen, id",
+        )?;
+        let observations = statement
+            .query_map([ip.to_string()], observation_from_row)?
+            .collect::<Result<Vec<Observation>, rusqlite::Error>>()?;
+        Ok(observations)
+    }
+
+    /// Every address and name a MAC address has been seen with
+    pub fn mac_sightings(&self, mac: &str) -> Result<Vec<Sighting>, InventoryError> {
+        self.sightings(
+            "ip",
+            "WHERE mac = ?1 GROUP BY name, mac, ip ORDER BY min(seen)",
+            &normalize_mac(mac),
+        )
+    }
+
+    /// When a MAC address was first and last seen, if it ever was
+    pub fn mac_seen(&self, mac: &str) -> Result<Option<Seen>, InventoryError> {
+        let seen = self
+            .connection
+            .query_row(
+                "SELECT first_seen, last_seen, last_ip FROM macs WHERE mac = ?1",
+                [normalize_mac(mac)],
+                |row| seen_from_row(row, 0),
+            )
+            .optional()?;
+        Ok(seen)
+    }
+
+    /// When each suffix of a NetBIOS name was first and last seen
+    pub fn name_seen(&self, name: &str) -> Result<Vec<(u8, Seen)>, InventoryError> {
+        let mut statement = self.connection.prepare(
+            "SELECT suffix, first_seen, last_seen, last_ip FROM names
+             WHERE name = ?1 ORDER BY suffix",
+        )?;
+        let seen = statement
+            .query_map([name.trim().to_uppercase()], |row| {
+                Ok((row.get(0)?, seen_from_row(row, 1)?))
+            })?
+            .collect::<Result<Vec<(u8, Seen)>, rusqlite::Error>>()?;
+        Ok(seen)
+    }
+
+    /// Hosts that haven't answered any scan since `cutoff`
+    pub fn not_seen_since(&self, cutoff: SystemTime) -> Result<Vec<Sighting>, InventoryError> {
+        self.sightings(
+            "(SELECT latest.ip FROM observations latest
+              WHERE latest.name = observations.name AND latest.mac = observations.mac
+              ORDER BY latest.seen DESC, latest.scan_id DESC LIMIT 1)",
+            "GROUP BY name, mac HAVING max(seen) < ?1 ORDER BY max(seen)",
+            &(unix_seconds(cutoff) as i64),
+        )
+    }
+
+    /// Groups the observations by `clauses`, with `ip` as the expression
+    /// that gives each group's address
+    fn sightings(&self, ip: &str, clauses: &str, parameter: &dyn ToSql) -> Result<Vec<Sighting>, InventoryError> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT name, mac, {}, min(seen), max(seen), count(DISTINCT scan_id)
+             FROM observations {}",
+            ip, clauses
+        ))?;
+        let sightings = statement
+            .query_map([parameter], |row| {
+                Ok(Sighting {
+                    name: row.get(0)?,
+                    mac: row.get(1)?,
+                    ip: parse_ip(row.get(2)?),
+                    first_seen: row.get::<_, i64>(3)? as u64,
+                    last_seen: row.get::<_, i64>(4)? as u64,
+                    times_seen: row.get::<_, i64>(5)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<Sighting>, rusqlite::Error>>()?;
+        Ok(sightings)
+    }
+}
+
+fn observation_from_row(row: &rusqlite::Row) -> rusqlite::Result<Observation> {
+    Ok(Observation {
+        scan_id: row.get(0)?,
+        seen: row.get::<_, i64>(1)? as u64,
+        ip: parse_ip(row.get(2)?),
+        name: row.get(3)?,
+        workgroup: row.get(4)?,
+        mac: row.get(5)?,
+    })
+}
+
+/// Reads first_seen, last_seen and last_ip from the columns starting at `first`
+fn seen_from_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Seen> {
+    Ok(Seen {
+        first_seen: row.get::<_, i64>(first)? as u64,
+        last_seen: row.get::<_, i64>(first + 1)? as u64,
+        last_ip: parse_ip(row.get(first + 2)?),
+    })
+}
+
+fn parse_ip(ip: String) -> Ipv4Addr {
+    ip.parse().unwrap_or(Ipv4Addr::UNSPECIFIED)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// MACs are stored the way `mac_address()` formats them, `2C:41:38:BA:C3:64`
+fn normalize_mac(mac: &str) -> String {
+    mac.trim().to_uppercase().replace('-', ":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+
+    fn result(ip: Ipv4Addr, mac_last_byte: u8) -> ScanResult {
+        let packet = packet(
+            ip,
+            &[(b"FILESERVER     ", 0x20, 0x0400)],
+            [0x2C, 0x41, 0x38, 0xBA, 0xC3, mac_last_byte],
+        );
+        ScanResult::new(packet, Duration::from_millis(3))
+    }
+
+    fn scan_at(seconds: u64) -> ScanInfo {
+        ScanInfo {
+            command_line: String::from("nbtscanner 10.1.2.0/24"),
+            started: UNIX_EPOCH + Duration::from_secs(seconds - 60),
+            finished: UNIX_EPOCH + Duration::from_secs(seconds),
+            targets: 254,
+        }
+    }
+
+    #[test]
+    fn tracks_hosts_across_scans() {
+        let mut inventory = Inventory::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let first = Ipv4Addr::new(10, 1, 2, 3);
+        let second = Ipv4Addr::new(10, 1, 2, 40);
+        inventory.record_scan(&scan_at(1_000_000), &[result(first, 0x64)]).unwrap();
+        let scan_id = inventory.record_scan(&scan_at(2_000_000), &[result(second, 0x64)]).unwrap();
+
+        assert_eq!(inventory.scans().unwrap().len(), 2);
+        assert_eq!(inventory.scan_results(scan_id).unwrap()[0].ip, second);
+        assert!(matches!(inventory.scan_results(7), Err(UnknownScanError(7))));
+
+        let history = inventory.ip_history(first).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].name, "FILESERVER");
+
+        let seen = inventory.mac_seen("2c-41-38-ba-c3-64").unwrap().unwrap();
+        assert_eq!((seen.first_seen, seen.last_seen, seen.last_ip), (1_000_000, 2_000_000, second));
+        assert_eq!(inventory.mac_seen("00:00:00:00:00:00").unwrap(), None);
+        let names = inventory.name_seen("fileserver").unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].0, 0x20);
+        assert_eq!(names[0].1, seen);
+
+        let sightings = inventory.mac_sightings("2c-41-38-ba-c3-64").unwrap();
+        assert_eq!(sightings.len(), 2);
+        assert_eq!((sightings[0].ip, sightings[1].ip), (first, second));
+
+        let stale = inventory.not_seen_since(UNIX_EPOCH + Duration::from_secs(3_000_000)).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!((stale[0].ip, stale[0].first_seen, stale[0].times_seen), (second, 1_000_000, 2));
+        assert!(inventory
+            .not_seen_since(UNIX_EPOCH + Duration::from_secs(1_500_000))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn stale_hosts_show_their_latest_address() {
+        let mut inventory = Inventory::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let latest = Ipv4Addr::new(10, 1, 2, 40);
+        inventory.record_scan(&scan_at(2_000_000), &[result(latest, 0x64)]).unwrap();
+        for (seconds, octet) in [(1_000_000, 3), (1_500_000, 9)] {
+            let ip = Ipv4Addr::new(10, 1, 2, octet);
+            inventory.record_scan(&scan_at(seconds), &[result(ip, 0x64)]).unwrap();
+        }
+
+        let stale = inventory.not_seen_since(UNIX_EPOCH + Duration::from_secs(3_000_000)).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!((stale[0].ip, stale[0].last_seen, stale[0].times_seen), (latest, 2_000_000, 3));
+    }
+}
@@ -1,101 +1,390 @@
 use self::IpParserError::*;
+use crate::permutation::Permutation;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
+use std::fmt;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 use std::vec::Vec;
-use std::{fmt, u32, u8};
 
 pub fn parse_ip_string(ip_str: &str) -> IpParserResult<Vec<Ipv4Addr>, IpParserError> {
-    // check base ip
-    if ip_str.contains('-') {
-        let tokens: Vec<&str> = ip_str.split('-').collect();
-        let base_ip = match Ipv4Addr::from_str(tokens[0]) {
-            Ok(ip) => ip,
-            Err(_) => return Err(IpParserError::BaseIpError),
-        };
-        let last_octet = u8::from_str(tokens[1]).unwrap(); // need to handle this
-        parse_ip_string_with_dash(base_ip, last_octet)
-    } else if ip_str.contains('/') {
-        let tokens: Vec<&str> = ip_str.split('/').collect();
-        let base_ip = match Ipv4Addr::from_str(tokens[0]) {
-            Ok(ip) => ip,
-            Err(_) => return Err(IpParserError::BaseIpError),
-        };
-        let mask = u8::from_str(tokens[1]).unwrap();
-        parse_ip_string_with_cidr(base_ip, mask)
-    } else {
-        // Single IP strings
-        match Ipv4Addr::from_str(ip_str) {
-            Ok(ip) => Ok(vec![ip]),
-            Err(_) => Err(IpParserError::BaseIpError),
-        }
-    }
+    let targets = TargetSet::from_str(ip_str)?;
+    Ok(targets.iter().collect())
 }
 
 #[derive(Debug)]
 pub enum IpParserError {
     CidrNumberError,
     BaseIpError,
+    RangeEndError,
+    ShardError,
+}
+
+impl Error for IpParserError {}
+
+impl fmt::Display for IpParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let description = match *self {
+            CidrNumberError => "The provided CIDR number must be between 15 and 29",
+            BaseIpError => "The base IP provided was not a valid IP address",
+            RangeEndError => "The end of a dashed range must be a last octet from 0 to 255",
+            ShardError => "The shard must be given as K/N, with K between 1 and N",
+        };
+        description.fmt(f)
+    }
+}
+
+pub type IpParserResult<T, IpParserError> = Result<T, IpParserError>;
+
+/// A contiguous run of addresses, positioned within the whole target set
+#[derive(Debug, Clone)]
+struct Span {
+    first: u32,
+    count: u64,
+    offset: u64,
 }
 
-impl Error for IpParserError {
-    fn description(&self) -> &str {
-        match *self {
-            CidrNumberError => {
-                "The provided CIDR number cannot be greater than 32, and not less than 15"
-            },
-            BaseIpError => {
-                "The base IP provided was not a valid IP address"
+/// The addresses to scan, kept as ranges rather than expanded.
+///
+/// Addresses are indexed in ascending order, so a target can be looked up by
+/// its position without materializing the list. Targets that were given by
+/// name remember the hostname they were resolved from.
+#[derive(Debug, Clone, Default)]
+pub struct TargetSet {
+    spans: Vec<Span>,
+    hostnames: BTreeMap<Ipv4Addr, String>,
+}
+
+impl TargetSet {
+    pub fn new() -> TargetSet {
+        TargetSet::default()
+    }
+
+    /// Adds every host address of the subnet, skipping the network and
+    /// broadcast addresses
+    pub fn add_subnet(&mut self, base_ip: Ipv4Addr, mask: u8) -> IpParserResult<(), IpParserError> {
+        if !(15..=29).contains(&mask) {
+            return Err(IpParserError::CidrNumberError);
+        }
+        let host_mask = u32::MAX >> mask;
+        let start = u32::from(base_ip) & !host_mask;
+        let end = u32::from(base_ip) | host_mask;
+        self.add_range(Ipv4Addr::from(start + 1), Ipv4Addr::from(end - 1));
+        Ok(())
+    }
+
+    /// Adds all addresses from `first` to `last` inclusive
+    pub fn add_range(&mut self, first: Ipv4Addr, last: Ipv4Addr) {
+        let (first, last) = (u32::from(first), u32::from(last));
+        if first > last {
+            return;
+        }
+
+        let mut bounds: Vec<(u32, u32)> = self
+            .spans
+            .iter()
+            .map(|span| (span.first, span.first + (span.count - 1) as u32))
+            .collect();
+        bounds.push((first, last));
+        bounds.sort_unstable();
+
+        // Merge overlapping and adjacent ranges so every address appears once
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(bounds.len());
+        for (first, last) in bounds {
+            match merged.last_mut() {
+                Some(previous) if u64::from(first) <= u64::from(previous.1) + 1 => {
+                    previous.1 = previous.1.max(last)
+                }
+                _ => merged.push((first, last)),
+            }
+        }
+
+        let mut offset = 0;
+        self.spans = merged
+            .into_iter()
+            .map(|(first, last)| {
+                let count = u64::from(last - first) + 1;
+                let span = Span { first, count, offset };
+                offset += count;
+                span
+            })
+            .collect();
+    }
+
+    /// Adds a single address that was given as (or resolved from) `hostname`
+    pub fn add_host(&mut self, ip: Ipv4Addr, hostname: &str) {
+        self.add_range(ip, ip);
+        self.hostnames
+            .entry(ip)
+            .or_insert_with(|| String::from(hostname));
+    }
+
+    /// Adds every address and hostname of `other` to this set
+    pub fn merge(&mut self, other: &TargetSet) {
+        for (first, last) in other.ranges() {
+            self.add_range(first, last);
+        }
+        for (ip, hostname) in other.hostnames.iter() {
+            self.hostnames.entry(*ip).or_insert_with(|| hostname.clone());
+        }
+    }
+
+    /// The hostname the target was given as, if it wasn't given by address
+    pub fn hostname(&self, ip: Ipv4Addr) -> Option<&str> {
+        self.hostnames.get(&ip).map(String::as_str)
+    }
+
+    pub fn hostnames(&self) -> &BTreeMap<Ipv4Addr, String> {
+        &self.hostnames
+    }
+
+    pub fn has_hostnames(&self) -> bool {
+        !self.hostnames.is_empty()
+    }
+
+    pub fn len(&self) -> u64 {
+        self.spans.last().map_or(0, |span| span.offset + span.count)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    pub fn first(&self) -> Option<Ipv4Addr> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<Ipv4Addr> {
+        self.len().checked_sub(1).and_then(|index| self.get(index))
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let ip = u32::from(ip);
+        self.spans
+            .iter()
+            .any(|span| ip >= span.first && u64::from(ip - span.first) < span.count)
+    }
+
+    /// Returns the address at `index` in ascending order
+    pub fn get(&self, index: u64) -> Option<Ipv4Addr> {
+        let position = self
+            .spans
+            .partition_point(|span| span.offset + span.count <= index);
+        self.spans
+            .get(position)
+            .map(|span| Ipv4Addr::from(span.first + (index - span.offset) as u32))
+    }
+
+    /// Returns this shard's part of the set. The set is split into
+    /// `shard.count` contiguous blocks of (nearly) equal size, so every
+    /// address belongs to exactly one shard.
+    pub fn shard(&self, shard: Shard) -> TargetSet {
+        let len = u128::from(self.len());
+        let start = (len * u128::from(shard.index) / u128::from(shard.count)) as u64;
+        let end = (len * u128::from(shard.index + 1) / u128::from(shard.count)) as u64;
+
+        let mut sharded = TargetSet::new();
+        for span in self.spans.iter() {
+            let first = start.max(span.offset);
+            let last = end.min(span.offset + span.count);
+            if first < last {
+                sharded.add_range(
+                    Ipv4Addr::from(span.first + (first - span.offset) as u32),
+                    Ipv4Addr::from(span.first + (last - 1 - span.offset) as u32),
+                );
             }
         }
+        for (ip, hostname) in self.hostnames.iter() {
+            if sharded.contains(*ip) {
+                sharded.hostnames.insert(*ip, hostname.clone());
+            }
+        }
+        sharded
+    }
+
+    /// Returns the set without the addresses in `excluded`, keeping the
+    /// hostnames of the rest
+    pub fn without(&self, excluded: &BTreeSet<Ipv4Addr>) -> TargetSet {
+        let mut kept: Vec<(u32, u32)> = Vec::new();
+        for span in self.spans.iter() {
+            let last = span.first + (span.count - 1) as u32;
+            // u64 so the address after 255.255.255.255 doesn't overflow
+            let mut next = u64::from(span.first);
+            for ip in excluded.range(Ipv4Addr::from(span.first)..=Ipv4Addr::from(last)) {
+                let ip = u64::from(u32::from(*ip));
+                if ip > next {
+                    kept.push((next as u32, (ip - 1) as u32));
+                }
+                next = ip + 1;
+            }
+            if next <= u64::from(last) {
+                kept.push((next as u32, last));
+            }
+        }
+
+        let mut offset = 0;
+        let spans = kept
+            .into_iter()
+            .map(|(first, last)| {
+                let count = u64::from(last - first) + 1;
+                let span = Span { first, count, offset };
+                offset += count;
+                span
+            })
+            .collect();
+        let hostnames = self
+            .hostnames
+            .iter()
+            .filter(|(ip, _)| !excluded.contains(ip))
+            .map(|(ip, hostname)| (*ip, hostname.clone()))
+            .collect();
+        TargetSet { spans, hostnames }
+    }
+
+    /// The contiguous ranges making up the set, in ascending order
+    pub fn ranges(&self) -> Vec<(Ipv4Addr, Ipv4Addr)> {
+        self.spans
+            .iter()
+            .map(|span| {
+                let last = span.first + (span.count - 1) as u32;
+                (Ipv4Addr::from(span.first), Ipv4Addr::from(last))
+            })
+            .collect()
+    }
+
+    /// Iterates over the targets in ascending order
+    pub fn iter(&self) -> Targets<'_> {
+        Targets {
+            targets: self,
+            permutation: None,
+            position: 0,
+        }
+    }
+
+    /// Iterates over the targets in a pseudo-random order that is fully
+    /// determined by `seed`. The order is computed on the fly, so memory use
+    /// doesn't grow with the size of the set.
+    pub fn shuffled(&self, seed: u64) -> Targets<'_> {
+        Targets {
+            targets: self,
+            permutation: Some(Permutation::new(self.len(), seed)),
+            position: 0,
+        }
     }
 }
 
-impl fmt::Display for IpParserError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.to_string().fmt(f)
+impl FromStr for TargetSet {
+    type Err = IpParserError;
+
+    fn from_str(ip_str: &str) -> IpParserResult<TargetSet, IpParserError> {
+        let mut targets = TargetSet::new();
+        // check base ip
+        if ip_str.contains('-') {
+            let tokens: Vec<&str> = ip_str.splitn(2, '-').collect();
+            let base_ip = match Ipv4Addr::from_str(tokens[0]) {
+                Ok(ip) => ip,
+                Err(_) => return Err(IpParserError::BaseIpError),
+            };
+            let last_octet = u8::from_str(tokens[1]).map_err(|_| IpParserError::RangeEndError)?;
+            let mut last_ip = base_ip.octets();
+            last_ip[3] = last_octet;
+            targets.add_range(base_ip, Ipv4Addr::from(last_ip));
+        } else if ip_str.contains('/') {
+            let tokens: Vec<&str> = ip_str.splitn(2, '/').collect();
+            let base_ip = match Ipv4Addr::from_str(tokens[0]) {
+                Ok(ip) => ip,
+                Err(_) => return Err(IpParserError::BaseIpError),
+            };
+            let mask = u8::from_str(tokens[1]).map_err(|_| IpParserError::CidrNumberError)?;
+            targets.add_subnet(base_ip, mask)?;
+        } else {
+            // Single IP strings
+            match Ipv4Addr::from_str(ip_str) {
+                Ok(ip) => targets.add_range(ip, ip),
+                Err(_) => return Err(IpParserError::BaseIpError),
+            }
+        }
+        Ok(targets)
     }
 }
 
-pub type IpParserResult<T, IpParserError> = Result<T, IpParserError>;
+/// One of `count` disjoint parts of a target set, written as `K/N` where K
+/// starts from 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
 
-fn parse_ip_string_with_dash(
-    base_ip: Ipv4Addr,
-    ending_octet: u8,
-) -> IpParserResult<Vec<Ipv4Addr>, IpParserError> {
-    let mut range: Vec<Ipv4Addr> = Vec::new();
-    let starting_octet = base_ip.octets()[3];
-    for n in starting_octet..(ending_octet + 1) {
-        let mut octets = base_ip.octets().clone();
-        octets[3] = n;
-        range.push(Ipv4Addr::from(octets));
-    }
-    Ok(range)
+impl Shard {
+    pub fn new(number: u64, count: u64) -> IpParserResult<Shard, IpParserError> {
+        if number == 0 || number > count {
+            return Err(IpParserError::ShardError);
+        }
+        Ok(Shard {
+            index: number - 1,
+            count,
+        })
+    }
+}
+
+impl FromStr for Shard {
+    type Err = IpParserError;
+
+    fn from_str(shard_str: &str) -> IpParserResult<Shard, IpParserError> {
+        let mut tokens = shard_str.splitn(2, '/');
+        let number = tokens.next().and_then(|number| u64::from_str(number).ok());
+        let count = tokens.next().and_then(|count| u64::from_str(count).ok());
+        match (number, count) {
+            (Some(number), Some(count)) => Shard::new(number, count),
+            _ => Err(IpParserError::ShardError),
+        }
+    }
 }
 
-fn parse_ip_string_with_cidr(
-    base_ip: Ipv4Addr,
-    mask: u8,
-) -> IpParserResult<Vec<Ipv4Addr>, IpParserError> {
-    if mask < 15 || mask > 29 {
-        return Err(IpParserError::CidrNumberError);
-    }
-    let raw_ip = u32::from(base_ip);
-    let mut bin_mask = 0u32;
-    for _ in 0..(32 - mask) {
-        // lookup table might be better here
-        bin_mask <<= 1; // is there no way to do this in one step?
-        bin_mask |= 1;
-    }
-    let start = raw_ip & !bin_mask;
-    let end = raw_ip | bin_mask;
-    let mut range: Vec<Ipv4Addr> = Vec::new();
-    for n in (start + 1)..end {
-        range.push(Ipv4Addr::from(n));
-    }
-    Ok(range)
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index + 1, self.count)
+    }
 }
 
+/// A lazy iterator over a `TargetSet`
+pub struct Targets<'a> {
+    targets: &'a TargetSet,
+    permutation: Option<Permutation>,
+    position: u64,
+}
+
+impl<'a> Iterator for Targets<'a> {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Ipv4Addr> {
+        if self.position >= self.targets.len() {
+            return None;
+        }
+        let index = match self.permutation {
+            Some(ref permutation) => permutation.apply(self.position),
+            None => self.position,
+        };
+        self.position += 1;
+        self.targets.get(index)
+    }
+
+    // Targets can be looked up by position, so skipping ahead (e.g. when
+    // resuming a scan) doesn't need to walk the skipped ones
+    fn nth(&mut self, n: usize) -> Option<Ipv4Addr> {
+        self.position = self.position.saturating_add(n as u64);
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.targets.len().saturating_sub(self.position) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for Targets<'a> {}
+
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
@@ -164,4 +453,83 @@ mod tests {
         let actual = parse_ip_string(str);
         assert_matches!(actual, Err(IpParserError::BaseIpError))
     }
+
+    #[test]
+    fn malformed_range_ends_return_errors() {
+        assert_matches!(parse_ip_string("10.0.0.1-300"), Err(IpParserError::RangeEndError));
+        assert_matches!(parse_ip_string("10.0.0.1-"), Err(IpParserError::RangeEndError));
+        assert_matches!(parse_ip_string("10.0.0.1-10.0.0.9"), Err(IpParserError::RangeEndError));
+        assert_matches!(parse_ip_string("10.0.0.1/abc"), Err(IpParserError::CidrNumberError));
+        assert_matches!(parse_ip_string("10.0.0.1/"), Err(IpParserError::CidrNumberError));
+    }
+
+    #[test]
+    fn dashed_range_can_end_on_the_last_octet() {
+        let actual = parse_ip_string("10.192.4.250-255").unwrap();
+        assert_eq!(actual.len(), 6);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_only_counted_once() {
+        let mut targets = TargetSet::from_str("10.192.4.1/24").unwrap();
+        targets.add_range(Ipv4Addr::new(10, 192, 4, 200), Ipv4Addr::new(10, 192, 5, 9));
+        assert_eq!(targets.len(), 265);
+        assert_eq!(targets.get(255), Some(Ipv4Addr::new(10, 192, 5, 0)));
+        assert_eq!(targets.last(), Some(Ipv4Addr::new(10, 192, 5, 9)));
+    }
+
+    #[test]
+    fn shuffled_targets_cover_the_whole_set() {
+        let targets = TargetSet::from_str("10.192.4.1/24").unwrap();
+        let mut shuffled: Vec<Ipv4Addr> = targets.shuffled(1234).collect();
+        assert_ne!(shuffled, targets.iter().collect::<Vec<Ipv4Addr>>());
+        shuffled.sort();
+        assert_eq!(shuffled, targets.iter().collect::<Vec<Ipv4Addr>>());
+    }
+
+    #[test]
+    fn shards_cover_the_set_exactly_once() {
+        let mut targets = TargetSet::from_str("10.192.4.1/24").unwrap();
+        targets.add_range(Ipv4Addr::new(10, 192, 9, 1), Ipv4Addr::new(10, 192, 9, 7));
+        let sharded: Vec<Ipv4Addr> = (1..=3)
+            .flat_map(|k| targets.shard(Shard::new(k, 3).unwrap()).iter().collect::<Vec<_>>())
+            .collect();
+        assert_eq!(sharded, targets.iter().collect::<Vec<Ipv4Addr>>());
+        assert_eq!(targets.shard(Shard::new(1, 3).unwrap()).len(), 87);
+    }
+
+    #[test]
+    fn parse_invalid_shard() {
+        assert_matches!("0/3".parse::<Shard>(), Err(IpParserError::ShardError));
+        assert_matches!("4/3".parse::<Shard>(), Err(IpParserError::ShardError));
+        assert_matches!("2".parse::<Shard>(), Err(IpParserError::ShardError));
+    }
+
+    #[test]
+    fn skipping_ahead_keeps_the_shuffled_order() {
+        let targets = TargetSet::from_str("10.192.4.1/24").unwrap();
+        let all: Vec<Ipv4Addr> = targets.shuffled(99).collect();
+        let rest: Vec<Ipv4Addr> = targets.shuffled(99).skip(200).collect();
+        assert_eq!(rest, all[200..].to_vec());
+    }
+
+    #[test]
+    fn removes_excluded_addresses() {
+        let targets: TargetSet = "10.1.2.1-10".parse().unwrap();
+        let excluded: BTreeSet<Ipv4Addr> =
+            [Ipv4Addr::new(10, 1, 2, 1), Ipv4Addr::new(10, 1, 2, 5), Ipv4Addr::new(10, 1, 2, 6)]
+                .iter()
+                .cloned()
+                .collect();
+        let remaining = targets.without(&excluded);
+        assert_eq!(remaining.len(), 7);
+        assert_eq!(
+            remaining.ranges(),
+            vec![
+                (Ipv4Addr::new(10, 1, 2, 2), Ipv4Addr::new(10, 1, 2, 4)),
+                (Ipv4Addr::new(10, 1, 2, 7), Ipv4Addr::new(10, 1, 2, 10)),
+            ]
+        );
+        assert_eq!(remaining.get(3), Some(Ipv4Addr::new(10, 1, 2, 7)));
+    }
 }
@@ -0,0 +1,136 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Upper bounds of the histogram buckets in milliseconds, roughly doubling,
+/// with a last bucket for everything slower
+const BUCKETS_MS: [u64; 9] = [1, 2, 5, 10, 20, 50, 100, 200, 500];
+
+/// The widest bar in the histogram
+const BAR_WIDTH: usize = 40;
+
+/// How quickly the hosts that answered did so
+#[derive(Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+    /// The number of round trips in each of `BUCKETS_MS` and one more for
+    /// the slower ones
+    pub histogram: Vec<usize>,
+}
+
+impl LatencyStats {
+    /// The statistics of these round trip times, if there are any
+    pub fn of(rtts: &[Duration]) -> Option<LatencyStats> {
+        if rtts.is_empty() {
+            return None;
+        }
+        let mut sorted = rtts.to_vec();
+        sorted.sort();
+
+        let mut histogram = vec![0; BUCKETS_MS.len() + 1];
+        for rtt in sorted.iter() {
+            let bucket = BUCKETS_MS
+                .iter()
+                .position(|bound| *rtt < Duration::from_millis(*bound))
+                .unwrap_or(BUCKETS_MS.len());
+            histogram[bucket] += 1;
+        }
+
+        Some(LatencyStats {
+            count: sorted.len(),
+            min: sorted[0],
+            median: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            max: sorted[sorted.len() - 1],
+            histogram,
+        })
+    }
+
+    /// Writes the figures on one line followed by the histogram, leaving out
+    /// the empty buckets below the fastest and above the slowest reply
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "Latency over {} repl{}: min {}, median {}, p95 {}, max {}",
+            self.count,
+            if self.count == 1 { "y" } else { "ies" },
+            millis(self.min),
+            millis(self.median),
+            millis(self.p95),
+            millis(self.max)
+        )?;
+        let first = self.histogram.iter().position(|count| *count > 0).unwrap_or(0);
+        let last = self.histogram.iter().rposition(|count| *count > 0).unwrap_or(0);
+        let most = self.histogram.iter().cloned().max().unwrap_or(0).max(1);
+        for bucket in first..=last {
+            let count = self.histogram[bucket];
+            // Any non-empty bucket gets at least one mark
+            let width = (count * BAR_WIDTH).div_ceil(most);
+            writeln!(
+                out,
+                "  {:>10} {:<width$} {}",
+                bucket_label(bucket),
+                "#".repeat(width),
+                count,
+                width = BAR_WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The nearest-rank percentile of sorted values
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        0 => format!("< {} ms", BUCKETS_MS[0]),
+        _ if bucket == BUCKETS_MS.len() => format!(">= {} ms", BUCKETS_MS[bucket - 1]),
+        _ => format!("{}-{} ms", BUCKETS_MS[bucket - 1], BUCKETS_MS[bucket]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_round_trip_times() {
+        assert_eq!(LatencyStats::of(&[]), None);
+
+        let rtts: Vec<Duration> = [12, 3, 7, 1, 600, 4, 3, 9, 2, 5]
+            .iter()
+            .map(|ms| Duration::from_millis(*ms))
+            .collect();
+        let stats = LatencyStats::of(&rtts).unwrap();
+        assert_eq!(stats.count, 10);
+        assert_eq!(stats.min, Duration::from_millis(1));
+        assert_eq!(stats.median, Duration::from_millis(4));
+        assert_eq!(stats.p95, Duration::from_millis(600));
+        assert_eq!(stats.max, Duration::from_millis(600));
+        assert_eq!(stats.histogram, vec![0, 1, 4, 3, 1, 0, 0, 0, 0, 1]);
+
+        let mut out = Vec::new();
+        stats.write(&mut out).unwrap();
+        let written = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(
+            lines[0],
+            "Latency over 10 replies: min 1.000 ms, median 4.000 ms, p95 600.000 ms, max 600.000 ms"
+        );
+        // From the 1-2 ms bucket to the last one
+        assert_eq!(lines.len(), 10);
+        assert!(lines[1].starts_with("      1-2 ms ########## "));
+        assert!(lines[9].ends_with(" 1"));
+    }
+}
@@ -1,14 +1,50 @@
+use std::collections::BTreeSet;
+use std::io;
 use std::net::Ipv4Addr;
 use std::net::UdpSocket;
-use std::time::Duration;
+use std::thread;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
-mod nbt_packet;
+pub mod checkpoint;
+pub mod diff;
+pub mod filter;
+pub mod fingerprint;
+mod hex;
+pub mod inventory;
+pub mod ip_range;
+pub mod latency;
+pub mod nbt_packet;
+pub mod oui;
+pub mod output;
+mod permutation;
+pub mod resolver;
+pub mod roles;
+pub mod scan_result;
+pub mod sort;
+mod status;
 mod thread_pool;
+pub mod timestamp;
+pub mod watch;
+pub mod webhook;
 
+use checkpoint::{Checkpoint, ScanOptions};
+use filter::Filter;
+use inventory::Inventory;
+use ip_range::TargetSet;
+use latency::LatencyStats;
 use nbt_packet::NetBiosPacket;
+use output::{OutputOptions, ScanInfo};
+use scan_result::{HostState, ScanResult, Unanswered};
+use sort::SortOrder;
+use status::StatusLine;
 use thread_pool::ThreadPool;
+use watch::{Event, Watcher};
+use webhook::Webhook;
 
 const NET_BIOS_PORT: u16 = 137;
+/// A node status request, with the transaction ID of the first attempt in
+/// its first two bytes
 const MESSAGE: [u8; 50] = [
     0xA2, 0x48, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x43, 0x4b, 0x41,
     0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
@@ -17,86 +53,589 @@ const MESSAGE: [u8; 50] = [
 ];
 const TIMEOUT_SECONDS: u64 = 2;
 const DEFAULT_THREADS: usize = 100;
+const CHECKPOINT_INTERVAL_SECONDS: u64 = 10;
 
 pub struct Config {
-    verbose: bool,
+    pub verbose: bool,
+    /// Probe the targets in a pseudo-random order derived from this seed
+    pub seed: Option<u64>,
+    /// How many more times to ask a target that didn't reply
+    pub retries: u32,
+    /// Periodically save the scan's progress to this file
+    pub checkpoint: Option<PathBuf>,
+    /// How the targets were given, recorded in checkpoints
+    pub target_spec: String,
+    /// Record the scan and its results in this inventory database
+    pub database: Option<PathBuf>,
+    /// Post the changes seen in watch mode to this endpoint
+    pub webhook: Option<Webhook>,
+    /// Only output the hosts that pass this filter
+    pub filter: Filter,
+    /// Output the targets that didn't answer instead of the hosts that did
+    pub only_silent: bool,
+    /// Also output the targets that are up with NetBIOS closed, or that
+    /// couldn't be probed
+    pub show_all: bool,
+    /// The order hosts are written in, except by the formats that stream
+    pub sort: SortOrder,
+    /// Report the users logged in to each host, from its messenger names
+    pub users: bool,
+    pub output: OutputOptions,
 }
 
 impl Config {
     pub fn new(verbose: bool) -> Config {
-        Config { verbose: verbose }
+        Config {
+            verbose,
+            seed: None,
+            retries: 0,
+            checkpoint: None,
+            target_spec: String::new(),
+            database: None,
+            webhook: None,
+            filter: Filter::new(),
+            only_silent: false,
+            show_all: false,
+            sort: SortOrder::default(),
+            users: false,
+            output: OutputOptions::default(),
+        }
+    }
+
+    /// The options a checkpoint records, which a resumed scan has to share
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            retries: self.retries,
+            users: self.users,
+            show_all: self.show_all,
+            only_silent: self.only_silent,
+            filter: self.filter.options(),
+        }
+    }
+}
+
+pub fn run(targets: &TargetSet, config: Config) {
+    eprintln!(
+        "Scanning from {} to {} ({} total)",
+        targets.first().unwrap(),
+        targets.last().unwrap(),
+        targets.len()
+    );
+
+    scan(targets, &config, 0, Vec::new(), Vec::new())
+}
+
+/// Continues the scan saved in `checkpoint`, with the seed and targets it
+/// was started with
+pub fn resume(checkpoint: Checkpoint, mut config: Config) {
+    let targets = checkpoint.targets();
+    config.seed = checkpoint.seed;
+    config.target_spec = checkpoint.spec.clone();
+
+    eprintln!(
+        "Resuming scan of {} at {} of {}",
+        checkpoint.spec,
+        checkpoint.next_position,
+        targets.len()
+    );
+
+    scan(
+        &targets,
+        &config,
+        checkpoint.next_position,
+        checkpoint.results(),
+        checkpoint.unanswered(),
+    )
+}
+
+fn scan(
+    targets: &TargetSet,
+    config: &Config,
+    start: u64,
+    results: Vec<ScanResult>,
+    unanswered: Vec<Unanswered>,
+) {
+    let started = SystemTime::now();
+    let scanned = collect(targets, config, start, results, unanswered, &mut |result| {
+        if config.only_silent || !config.filter.matches(result) {
+            return;
+        }
+        let stdout = io::stdout();
+        if let Err(e) = output::write_result(&config.output, result, &mut stdout.lock()) {
+            eprintln!("Couldn't write the result for {}: {}", result.ip, e);
+        }
+    });
+
+    let info = ScanInfo {
+        command_line: config.output.command_line.clone(),
+        started,
+        finished: SystemTime::now(),
+        targets: targets.len(),
+    };
+    let results = &scanned.results;
+    let stdout = io::stdout();
+    let written = if config.only_silent {
+        let answered: BTreeSet<Ipv4Addr> = results.iter().map(|result| result.ip).collect();
+        let silent: Vec<Ipv4Addr> = targets.without(&answered).iter().collect();
+        output::write_silent(&silent, &mut stdout.lock())
+    } else {
+        let mut wanted: Vec<ScanResult> =
+            results.iter().filter(|result| config.filter.matches(result)).cloned().collect();
+        config.sort.sort(&mut wanted);
+        // Hosts without names can't pass a filter
+        let unanswered: &[Unanswered] = if config.show_all && config.filter.is_empty() {
+            &scanned.unanswered
+        } else {
+            &[]
+        };
+        output::write_results(&config.output, &wanted, unanswered, &info, &mut stdout.lock())
+    };
+    if let Err(e) = written {
+        eprintln!("Couldn't write the results: {}", e);
+    }
+    report_states(&scanned);
+    report_errors(&scanned.unanswered);
+    report_latency(results);
+    record(config, &info, results);
+}
+
+/// Rescans the targets every `interval` until interrupted, writing only the
+/// hosts that appeared, vanished or changed since the round before
+pub fn watch(targets: &TargetSet, config: Config, interval: Duration) {
+    eprintln!(
+        "Watching {} to {} ({} total), rescanning every {} seconds",
+        targets.first().unwrap(),
+        targets.last().unwrap(),
+        targets.len(),
+        interval.as_secs()
+    );
+
+    let mut watcher = Watcher::new();
+    loop {
+        let round_started = Instant::now();
+        let started = SystemTime::now();
+        let due = watcher.due(targets);
+        let results = scan_with(&due, &config, &mut |_| {});
+
+        let info = ScanInfo {
+            command_line: config.output.command_line.clone(),
+            started,
+            finished: SystemTime::now(),
+            targets: due.len(),
+        };
+        record(&config, &info, &results);
+
+        // Hosts that don't pass the filter are watched as if they didn't answer
+        let wanted: Vec<ScanResult> = results.into_iter().filter(|result| config.filter.matches(result)).collect();
+        let changes = watcher.update(&due, &wanted);
+        if watcher.rounds() == 1 {
+            eprintln!("{} hosts answered, watching for changes", wanted.len());
+        } else if config.verbose {
+            eprintln!(
+                "Round {}: probed {} of {} targets, {} changes",
+                watcher.rounds(),
+                due.len(),
+                targets.len(),
+                changes.len()
+            );
+        }
+        let events: Vec<Event> = changes.into_iter().map(Event::new).collect();
+        let stdout = io::stdout();
+        for event in events.iter() {
+            if let Err(e) = output::write_event(&config.output, event, &mut stdout.lock()) {
+                eprintln!("Couldn't write a change: {}", e);
+            }
+        }
+        if let Some(ref webhook) = config.webhook {
+            match webhook.send(&events) {
+                Ok(0) => (),
+                Ok(sent) if config.verbose => eprintln!("Posted {} changes to {}", sent, webhook.url),
+                Ok(_) => (),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        thread::sleep(interval.saturating_sub(round_started.elapsed()));
     }
 }
 
-pub fn run(ips: Vec<Ipv4Addr>, config: Config) {
+/// Probes every target, passing each host to `on_result` as soon as it
+/// answers. Returns every host that answered, sorted by address.
+pub fn scan_with(
+    targets: &TargetSet,
+    config: &Config,
+    on_result: &mut dyn FnMut(&ScanResult),
+) -> Vec<ScanResult> {
+    collect(targets, config, 0, Vec::new(), Vec::new(), on_result).results
+}
+
+/// Everything a scan found, sorted by address
+struct Scanned {
+    results: Vec<ScanResult>,
+    unanswered: Vec<Unanswered>,
+    /// How many targets didn't reply at all, over the whole scan
+    no_reply: u64,
+    /// Whether the scan carried on from a checkpoint
+    resumed: bool,
+    /// How many targets were probed, leaving out those a resumed scan had
+    /// already done
+    sent: u64,
+    elapsed: Duration,
+}
+
+fn collect(
+    targets: &TargetSet,
+    config: &Config,
+    start: u64,
+    results: Vec<ScanResult>,
+    unanswered: Vec<Unanswered>,
+    on_result: &mut dyn FnMut(&ScanResult),
+) -> Scanned {
     let pool = ThreadPool::new(DEFAULT_THREADS);
     let verbose = config.verbose;
+    let retries = config.retries;
+    let mut progress = Progress::new(targets, config, start, results, unanswered);
+    // Verbose logging would scroll the line away
+    let mut status = StatusLine::new(targets.len() - start, !verbose);
+    let started = Instant::now();
 
-    println!(
-        "Scanning from {} to {} ({} total)",
-        ips.first().unwrap(),
-        ips.last().unwrap(),
-        ips.len()
-    );
+    let ips = match config.seed {
+        Some(seed) => targets.shuffled(seed),
+        None => targets.iter(),
+    };
 
-    for ip in ips {
-        // This closure here requires a Option<NetBiosPacket> to be returned
+    for (offset, ip) in ips.skip(start as usize).enumerate() {
+        let position = start + offset as u64;
+        progress.started(position);
         // These are executed asynchronously by the thread pool
-        pool.execute(move || {
-            // bind to port 0 and let the OS decide
-            let socket = UdpSocket::bind("0.0.0.0:0").expect("Couldn't bind UDP socket");
-            // timeout connection after 2 seconds
-            socket
-                .set_read_timeout(Some(Duration::new(TIMEOUT_SECONDS, 0)))
-                .ok();
-
-            let mut buf: [u8; 1024] = [0; 1024];
-            socket
-                .connect((ip, NET_BIOS_PORT))
-                .ok()
-                .expect("Couldn't connect to remote server");
-            if verbose {
-                println!("Contacting {}", ip);
+        pool.execute(move || (position, probe(ip, retries, verbose)));
+
+        while let Some((position, response)) = pool.try_result() {
+            if let Some(result) = progress.finished(position, response) {
+                status.clear();
+                on_result(result);
             }
+        }
+        status.update(progress.sent, progress.replies, progress.in_flight.len() as u64);
+        progress.save_checkpoint(false);
+    }
 
-            match socket.send(&MESSAGE) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!("Could not send data on the socket: {}", e);
-                    std::process::exit(-1)
+    pool.stop();
+
+    // Wait for the remaining probes to finish
+    while let Some((position, response)) = pool.next_result() {
+        if let Some(result) = progress.finished(position, response) {
+            status.clear();
+            on_result(result);
+        }
+        status.update(progress.sent, progress.replies, progress.in_flight.len() as u64);
+        progress.save_checkpoint(false);
+    }
+    pool.join_all();
+    status.clear();
+    progress.save_checkpoint(true);
+
+    let mut results = progress.results;
+    results.sort_by_key(|a| a.ip); // NOTE: This sort is in place hence the `mut` on results
+    // A resumed scan may probe a target again if it was in flight at the checkpoint
+    results.dedup_by_key(|a| a.ip);
+    let mut unanswered = progress.unanswered;
+    unanswered.sort_by_key(|a| a.ip);
+    unanswered.dedup_by_key(|a| a.ip);
+    // Counted from the targets left over rather than as replies time out, so
+    // the targets a resumed scan had already done are counted once
+    let heard_from: BTreeSet<Ipv4Addr> = results
+        .iter()
+        .map(|result| result.ip)
+        .chain(unanswered.iter().map(|unanswered| unanswered.ip))
+        .collect();
+    Scanned {
+        results,
+        unanswered,
+        no_reply: targets.len() - heard_from.len() as u64,
+        resumed: start > 0,
+        sent: progress.sent,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// Says how long the scan took and counts the targets in each state
+fn report_states(scanned: &Scanned) {
+    let count = |state: HostState| {
+        scanned
+            .unanswered
+            .iter()
+            .filter(|unanswered| unanswered.state == state)
+            .count()
+    };
+    let elapsed = scanned.elapsed.as_secs_f64();
+    // The states cover the targets done before resuming as well
+    let total = scanned.results.len() as u64 + scanned.unanswered.len() as u64 + scanned.no_reply;
+    eprintln!(
+        "Probed {}{} targets in {:.2} s ({:.0}/s){}: {} responding, {} closed, {} no reply, {} errors",
+        scanned.sent,
+        if scanned.resumed { " more" } else { "" },
+        elapsed,
+        scanned.sent as f64 / elapsed.max(0.001),
+        if scanned.resumed { format!(", {} in all", total) } else { String::new() },
+        scanned.results.len(),
+        count(HostState::Closed),
+        scanned.no_reply,
+        count(HostState::Error)
+    );
+}
+
+/// Says how many probes failed locally, since those targets weren't really
+/// scanned
+fn report_errors(unanswered: &[Unanswered]) {
+    let errors: Vec<&Unanswered> = unanswered
+        .iter()
+        .filter(|unanswered| unanswered.state == HostState::Error)
+        .collect();
+    if let Some(first) = errors.first() {
+        eprintln!(
+            "{} probes failed, the first to {}: {}",
+            errors.len(),
+            first.ip,
+            first.error.as_deref().unwrap_or("unknown error")
+        );
+    }
+}
+
+/// Prints how quickly the hosts answered, to spot slow links
+fn report_latency(results: &[ScanResult]) {
+    let rtts: Vec<Duration> = results.iter().map(|result| result.rtt).collect();
+    if let Some(stats) = LatencyStats::of(&rtts) {
+        let stderr = io::stderr();
+        stats.write(&mut stderr.lock()).ok();
+    }
+}
+
+/// Records the scan in the inventory, if there is one
+fn record(config: &Config, info: &ScanInfo, results: &[ScanResult]) {
+    if let Some(ref path) = config.database {
+        let recorded = Inventory::open(path).and_then(|mut inventory| inventory.record_scan(info, results));
+        match recorded {
+            Ok(scan_id) => eprintln!("Recorded scan {} in {}", scan_id, path.display()),
+            Err(e) => eprintln!("Couldn't record the scan in {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn probe(ip: Ipv4Addr, retries: u32, verbose: bool) -> Result<ScanResult, Unanswered> {
+    let unanswered = |state: HostState, error: Option<String>| Unanswered {
+        ip,
+        hostname: None,
+        state,
+        error,
+    };
+    let local_error = |what: &str, e: io::Error| {
+        if verbose {
+            eprintln!("{} for {}: {}", what, ip, e);
+        }
+        unanswered(HostState::Error, Some(format!("{}: {}", what, e)))
+    };
+
+    // bind to port 0 and let the OS decide
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| local_error("Couldn't bind UDP socket", e))?;
+    // timeout connection after 2 seconds
+    socket
+        .set_read_timeout(Some(Duration::new(TIMEOUT_SECONDS, 0)))
+        .ok();
+
+    let mut buf: [u8; 1024] = [0; 1024];
+    socket
+        .connect((ip, NET_BIOS_PORT))
+        .map_err(|e| local_error("Couldn't connect to remote server", e))?;
+    if verbose {
+        eprintln!("Contacting {}", ip);
+    }
+
+    // When each attempt was sent, so a late reply to an earlier one is timed
+    // from that attempt rather than the latest
+    let mut sent_at: Vec<Instant> = Vec::new();
+    loop {
+        socket
+            .send(&request(sent_at.len() as u32))
+            .map_err(|e| local_error("Could not send data on the socket", e))?;
+        sent_at.push(Instant::now());
+        let attempts = sent_at.len() as u32;
+
+        match socket.recv(&mut buf) {
+            Ok(number_of_bytes) => {
+                let rtt = round_trip(&buf[..number_of_bytes], &sent_at, Instant::now());
+                if verbose {
+                    eprintln!("Received response from {}", ip);
+                };
+                let packet = NetBiosPacket::from(ip, buf, number_of_bytes);
+                let mut result = ScanResult::new(packet, rtt);
+                result.attempts = attempts;
+                return Ok(result);
+            }
+            Err(error) => {
+                if verbose {
+                    eprintln!("Encountered an error when contacting {}: {:?}", ip, error);
+                };
+                // The socket is connected, so an ICMP port unreachable comes
+                // back as a refused connection
+                let unreachable = matches!(
+                    error.raw_os_error(),
+                    Some(libc::EHOSTUNREACH) | Some(libc::ENETUNREACH)
+                );
+                let state = match error.kind() {
+                    io::ErrorKind::ConnectionRefused => HostState::Closed,
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => HostState::NoReply,
+                    _ if unreachable => HostState::NoReply,
+                    _ => return Err(unanswered(HostState::Error, Some(error.to_string()))),
+                };
+                // Only silence is worth asking again, a closed port stays closed
+                if state == HostState::Closed || attempts > retries {
+                    return Err(unanswered(state, None));
                 }
             }
+        }
+    }
+}
 
-            match socket.recv(&mut buf) {
-                Ok(number_of_bytes) => {
-                    if verbose {
-                        println!("Received response from {}", ip);
-                    };
-                    let packet = NetBiosPacket::from(ip, buf.clone(), number_of_bytes);
-                    Some(packet)
-                }
-                Err(error) => {
-                    if verbose {
-                        println!("Encountered an error when contacting {}: {:?}", ip, error);
-                    };
-                    None
-                }
+/// The request for an attempt, each with its own transaction ID
+fn request(attempt: u32) -> [u8; 50] {
+    let mut request = MESSAGE;
+    let id = u16::from_be_bytes([MESSAGE[0], MESSAGE[1]]).wrapping_add(attempt as u16);
+    request[..2].copy_from_slice(&id.to_be_bytes());
+    request
+}
+
+/// How long a reply took, timed from the attempt whose transaction ID it
+/// carries. A reply that matches none is timed from the first attempt, so a
+/// slow host never looks faster than it is.
+fn round_trip(reply: &[u8], sent_at: &[Instant], received: Instant) -> Duration {
+    let attempt = match reply {
+        [high, low, ..] => {
+            let first = u16::from_be_bytes([MESSAGE[0], MESSAGE[1]]);
+            u16::from_be_bytes([*high, *low]).wrapping_sub(first) as usize
+        }
+        _ => 0,
+    };
+    let sent = sent_at.get(attempt).or_else(|| sent_at.first());
+    sent.map_or(Duration::ZERO, |sent| received.saturating_duration_since(*sent))
+}
+
+/// Tracks which targets have been probed so a checkpoint never skips a
+/// target that was still in flight when it was written
+struct Progress<'a> {
+    targets: &'a TargetSet,
+    config: &'a Config,
+    in_flight: BTreeSet<u64>,
+    next_position: u64,
+    results: Vec<ScanResult>,
+    /// The targets that were closed or couldn't be probed
+    unanswered: Vec<Unanswered>,
+    /// Probes sent and answered since the scan (re)started
+    sent: u64,
+    replies: u64,
+    last_saved: Instant,
+}
+
+impl<'a> Progress<'a> {
+    fn new(
+        targets: &'a TargetSet,
+        config: &'a Config,
+        start: u64,
+        mut results: Vec<ScanResult>,
+        unanswered: Vec<Unanswered>,
+    ) -> Progress<'a> {
+        if config.users {
+            for result in results.iter_mut() {
+                result.users = Some(result.packet.messenger_names());
             }
-        });
+        }
+        Progress {
+            targets,
+            config,
+            in_flight: BTreeSet::new(),
+            next_position: start,
+            results,
+            unanswered,
+            sent: 0,
+            replies: 0,
+            last_saved: Instant::now(),
+        }
     }
 
-    pool.stop();
+    fn started(&mut self, position: u64) {
+        self.in_flight.insert(position);
+        self.next_position = position + 1;
+        self.sent += 1;
+    }
+
+    fn finished(&mut self, position: u64, response: Result<ScanResult, Unanswered>) -> Option<&ScanResult> {
+        self.in_flight.remove(&position);
+        let mut result = match response {
+            Ok(result) => result,
+            Err(mut unanswered) => {
+                // Only counted at the end, there would be too many to keep
+                if unanswered.state != HostState::NoReply {
+                    unanswered.hostname = self.targets.hostname(unanswered.ip).map(String::from);
+                    self.unanswered.push(unanswered);
+                }
+                return None;
+            }
+        };
+        self.replies += 1;
+        result.hostname = self.targets.hostname(result.ip).map(String::from);
+        if self.config.users {
+            result.users = Some(result.packet.messenger_names());
+        }
+        self.results.push(result);
+        self.results.last()
+    }
 
-    // Wait for all worker threads to stop
-    let mut results = pool.join_all();
-    results.sort_by(|a, b| a.ip.cmp(&b.ip)); // NOTE: This sort is in place hence the `mut` on results
+    fn save_checkpoint(&mut self, force: bool) {
+        let path = match self.config.checkpoint {
+            Some(ref path) => path,
+            None => return,
+        };
+        if !force && self.last_saved.elapsed() < Duration::from_secs(CHECKPOINT_INTERVAL_SECONDS) {
+            return;
+        }
 
-    for result in results {
-        println!(
-            "{ip:<16}{group_and_name:<32}{mac:<15}",
-            ip = format!("{}", result.ip),
-            group_and_name = result.group_and_name(),
-            mac = result.mac_address()
+        let resume_from = match self.in_flight.iter().next() {
+            Some(&position) => position,
+            None => self.next_position,
+        };
+        let checkpoint = Checkpoint::new(
+            &self.config.target_spec,
+            self.targets,
+            self.config.seed,
+            self.config.scan_options(),
+            resume_from,
+            &self.results,
+            &self.unanswered,
         );
+        if let Err(e) = checkpoint.save(path) {
+            eprintln!("Couldn't save checkpoint to {}: {}", path.display(), e);
+        }
+        self.last_saved = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_replies_are_timed_from_their_own_attempt() {
+        let first = Instant::now();
+        let sent_at = [first, first + Duration::from_secs(2), first + Duration::from_secs(4)];
+        let received = first + Duration::from_millis(4100);
+
+        // The reply to the first attempt only arrived after the third was sent
+        let late = request(0);
+        assert_eq!(round_trip(&late, &sent_at, received), Duration::from_millis(4100));
+        let prompt = request(2);
+        assert_eq!(round_trip(&prompt, &sent_at, received), Duration::from_millis(100));
+        // An ID that matches no attempt, and a reply too short to carry one
+        assert_eq!(round_trip(&request(7), &sent_at, received), Duration::from_millis(4100));
+        assert_eq!(round_trip(&[0xA2], &sent_at, received), Duration::from_millis(4100));
     }
 }
@@ -2,11 +2,24 @@ extern crate assert_matches;
 extern crate clap;
 extern crate nbtscanner;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-mod ip_range;
+mod compare;
+mod filter_args;
+mod interfaces;
+mod queries;
 
-use nbtscanner::run;
+use nbtscanner::checkpoint::Checkpoint;
+use nbtscanner::fingerprint::{self, Rules};
+use nbtscanner::ip_range::{Shard, TargetSet};
+use nbtscanner::output::{Field, OutputFormat};
+use nbtscanner::oui::{self, OuiDatabase};
+use nbtscanner::resolver::{self, SystemResolver};
+use nbtscanner::{resume, run, watch};
+use nbtscanner::webhook::Webhook;
 use nbtscanner::Config;
 
 fn main() {
@@ -14,31 +27,403 @@ fn main() {
         .version("0.1")
         .author("Jon Grimes <jonkgrimes@gmail.com>")
         .about("Scans the given IP address range for NetBIOS information")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
         .arg(Arg::with_name("RANGE")
             .help("The IP address/range. This can be either be a range using the CIDR format (e.g. 10.10.1.2/24) or using a dash \
-                  (e.g. 10.10.2.1-254")
-            .required(true)
+                  (e.g. 10.10.2.1-254), or a hostname to resolve (e.g. fileserver.corp.local)")
+            .multiple(true)
+            .required_unless_one(&["local", "resume", "hosts-file"])
+        ).arg(Arg::with_name("hosts-file")
+            .short("f")
+            .long("hosts-file")
+            .value_name("FILE")
+            .help("Scan the hosts listed in an /etc/hosts style or LMHOSTS file")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+        ).arg(Arg::with_name("local")
+            .short("l")
+            .long("local")
+            .help("Scan the subnets of this host's IPv4 interfaces")
+            .required(false)
+        ).arg(Arg::with_name("interface")
+            .short("i")
+            .long("interface")
+            .help("Restrict --local to a single interface (e.g. eth0)")
+            .takes_value(true)
+            .requires("local")
+        ).arg(Arg::with_name("randomize-hosts")
+            .long("randomize-hosts")
+            .help("Probe the targets in a pseudo-random order instead of sequentially")
+            .required(false)
+        ).arg(Arg::with_name("seed")
+            .long("seed")
+            .help("Seed for --randomize-hosts, to repeat the same order")
+            .takes_value(true)
+            .requires("randomize-hosts")
+        ).arg(Arg::with_name("shard")
+            .long("shard")
+            .value_name("K/N")
+            .help("Only scan the Kth of N equal, disjoint parts of the targets")
+            .takes_value(true)
+        ).arg(Arg::with_name("retries")
+            .long("retries")
+            .value_name("N")
+            .help("Probe a target that didn't reply up to N more times (default 0)")
+            .takes_value(true)
+        ).arg(Arg::with_name("checkpoint")
+            .long("checkpoint")
+            .value_name("FILE")
+            .help("Periodically save the scan's progress to FILE so it can be resumed")
+            .takes_value(true)
+        ).arg(Arg::with_name("resume")
+            .long("resume")
+            .value_name("FILE")
+            .help("Continue the scan saved in the checkpoint FILE")
+            .takes_value(true)
+            .conflicts_with_all(&["RANGE", "hosts-file", "local", "randomize-hosts", "shard"])
+        ).arg(Arg::with_name("output-format")
+            .short("o")
+            .long("output-format")
+            .value_name("FORMAT")
+            .help("How to print the results: table, json, ndjson to stream one host per line, csv, tsv, \
+                  xml in nmap's format, names for each host's full name table, \
+                  lmhosts and hosts to generate those files, or summary for a report per workgroup")
+            .takes_value(true)
+            .default_value("table")
+        ).arg(Arg::with_name("all-names")
+            .short("A")
+            .long("all-names")
+            .help("Print each host's full name table like nbtstat -A, same as --output-format names")
+            .conflicts_with_all(&["output-format", "format"])
+        ).arg(Arg::with_name("columns")
+            .long("columns")
+            .value_name("LIST")
+            .help("Columns for csv/tsv output, from ip, hostname, name, workgroup, mac, vendor, fingerprint, roles, users, rtt, attempts, state, \
+                  and entry, suffix, type with --name-rows")
+            .takes_value(true)
+        ).arg(Arg::with_name("name-rows")
+            .long("name-rows")
+            .help("Write one csv/tsv row per name table entry instead of one per host")
+            .required(false)
+        ).arg(Arg::with_name("format")
+            .long("format")
+            .value_name("TEMPLATE")
+            .help("Print each host with a template like '{ip}\\t{entry}<{suffix}>\\t{mac}'. Fields can be \
+                  padded with {ip:<16} or {ip:>16}, cut off with {name:.8} and defaulted with {hostname|-}. \
+                  {#names}...{/names} repeats for each name entry, {?hostname}...{/hostname} is only \
+                  printed when the field has a value and {^hostname}...{/hostname} when it doesn't")
+            .takes_value(true)
+            .conflicts_with_all(&["output-format", "columns", "name-rows"])
+        ).arg(Arg::with_name("watch")
+            .long("watch")
+            .value_name("MINUTES")
+            .help("Keep rescanning the targets every MINUTES minutes and print only the hosts that appeared, \
+                  vanished or changed, as text or with --output-format ndjson. Hosts that stay unchanged \
+                  are rescanned less often")
+            .takes_value(true)
+            .conflicts_with_all(&["resume", "checkpoint", "format", "all-names"])
+        ).arg(Arg::with_name("webhook")
+            .long("webhook")
+            .value_name("URL")
+            .help("While watching, POST new hosts and changed name or MAC bindings to URL as JSON")
+            .takes_value(true)
+            .requires("watch")
+        ).arg(Arg::with_name("webhook-template")
+            .long("webhook-template")
+            .value_name("FILE")
+            .help("The webhook payload, with {events} replaced by the JSON array of changes, {summary} by \
+                  the changes as text escaped for a JSON string, and {count} by how many there are")
+            .takes_value(true)
+            .requires("webhook")
+        ).arg(Arg::with_name("db")
+            .long("db")
+            .value_name("FILE")
+            .help("Record the scan and every host that answered in a SQLite inventory, \
+                  see the inventory subcommand")
+            .takes_value(true)
+        ).arg(Arg::with_name("oui-file")
+            .long("oui-file")
+            .value_name("FILE")
+            .help("Look up MAC vendors in this IEEE oui.txt or Wireshark manuf file as well as the short built-in list")
+            .takes_value(true)
+        ).arg(Arg::with_name("fingerprints")
+            .long("fingerprints")
+            .value_name("FILE")
+            .help("Fingerprint hosts with the rules in FILE as well as the built-in ones, \
+                  see data/fingerprints.rules for the format")
+            .takes_value(true)
+        ).arg(Arg::with_name("sort")
+            .long("sort")
+            .value_name("KEYS")
+            .help("Sort the hosts by ip, name, workgroup, mac, rtt or vendor. Several keys can be given, \
+                  e.g. workgroup,name, and a key starting with - sorts in descending order, e.g. -rtt. \
+                  ndjson still prints hosts as they answer")
+            .takes_value(true)
+            .allow_hyphen_values(true)
+        ).args(&filter_args::args()
+        ).arg(Arg::with_name("only-responding")
+            .long("only-responding")
+            .help("Only show the hosts that answered, which is the default")
+        ).arg(Arg::with_name("only-silent")
+            .long("only-silent")
+            .help("Print the targets that didn't answer, one address per line, instead of the hosts that did")
+            .conflicts_with_all(&["only-responding", "watch", "format", "name-match", "workgroup",
+                                  "has-suffix", "vendor", "roles"])
+        ).arg(Arg::with_name("show-all")
+            .long("show-all")
+            .help("Also show the hosts that are up with NetBIOS closed (they answered with ICMP port \
+                  unreachable) and the targets that couldn't be probed")
+            .conflicts_with_all(&["only-responding", "only-silent", "format"])
+        ).arg(Arg::with_name("users")
+            .long("users")
+            .help("Report the users logged in to each host, from the messenger (<03>) names older Windows \
+                  registers for them. Off by default since it shows who is sitting where")
         ).arg(Arg::with_name("verbose")
             .short("v")
             .long("verbose")
             .help("Turn on verbose logging")
             .required(false)
+        ).subcommand(queries::subcommand()
+        ).subcommand(compare::subcommand()
         ).get_matches();
 
-    let raw_ip_str = matches.value_of("RANGE").unwrap();
+    if let Some(inventory_matches) = matches.subcommand_matches("inventory") {
+        return queries::run(inventory_matches);
+    }
+    if let Some(diff_matches) = matches.subcommand_matches("diff") {
+        return compare::run(diff_matches);
+    }
+
+    let verbose = matches.is_present("verbose");
+
+    if let Some(oui_path) = matches.value_of("oui-file") {
+        let mut database = OuiDatabase::embedded();
+        match OuiDatabase::load(Path::new(oui_path)) {
+            Ok(loaded) => database.merge(loaded),
+            Err(e) => {
+                println!("Couldn't read OUI file {}: {}", oui_path, e);
+                std::process::exit(-1)
+            }
+        }
+        oui::use_database(database);
+    }
+    if let Some(rules_path) = matches.value_of("fingerprints") {
+        let mut rules = Rules::embedded();
+        match Rules::load(Path::new(rules_path)) {
+            Ok(loaded) => rules.prepend(loaded),
+            Err(e) => {
+                println!("Couldn't read fingerprint rules {}: {}", rules_path, e);
+                std::process::exit(-1)
+            }
+        }
+        fingerprint::use_rules(rules);
+    }
 
-    let ips = match ip_range::parse_ip_string(raw_ip_str) {
-        Ok(ip_range) => ip_range,
+    let mut config = Config::new(verbose);
+    config.checkpoint = matches.value_of("checkpoint").map(PathBuf::from);
+    config.database = matches.value_of("db").map(PathBuf::from);
+    if let Some(retries) = matches.value_of("retries") {
+        config.retries = match retries.parse() {
+            Ok(retries) => retries,
+            Err(_) => {
+                println!("The number of retries must be a positive integer");
+                std::process::exit(-1)
+            }
+        };
+    }
+    config.users = matches.is_present("users");
+    config.filter = filter_args::filter(&matches);
+    config.only_silent = matches.is_present("only-silent");
+    config.show_all = matches.is_present("show-all");
+    if let Some(order) = matches.value_of("sort") {
+        config.sort = match order.parse() {
+            Ok(order) => order,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+    }
+    config.output.format = match matches.value_of("output-format").unwrap().parse() {
+        Ok(format) => format,
         Err(e) => {
             println!("{}", e);
             std::process::exit(-1)
         }
     };
+    if matches.is_present("all-names") {
+        config.output.format = OutputFormat::Names;
+    }
+    config.output.name_rows = matches.is_present("name-rows");
+    config.output.command_line = std::env::args().collect::<Vec<String>>().join(" ");
+    if let Some(template) = matches.value_of("format") {
+        config.output.template = match template.parse() {
+            Ok(template) => Some(template),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+    }
+    if let Some(columns) = matches.value_of("columns") {
+        config.output.fields = match Field::parse_list(columns) {
+            Ok(fields) => fields,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+    } else if config.output.name_rows {
+        config.output.fields.extend_from_slice(&[Field::Entry, Field::Suffix, Field::Type]);
+    }
 
-    let verbose = matches.is_present("verbose");
+    if let Some(resume_path) = matches.value_of("resume") {
+        let checkpoint = match Checkpoint::load(Path::new(resume_path)) {
+            Ok(checkpoint) => checkpoint,
+            Err(e) => {
+                println!("Couldn't read checkpoint {}: {}", resume_path, e);
+                std::process::exit(-1)
+            }
+        };
+        // Whatever was written before the interruption followed the saved
+        // options, so the rest of the scan has to as well
+        if checkpoint.options != config.scan_options() {
+            println!(
+                "The scan in {} was started with {}, but is being resumed with {}. \
+                 Resume it with the options it was started with",
+                resume_path,
+                checkpoint.options,
+                config.scan_options()
+            );
+            std::process::exit(-1)
+        }
+        // Keep saving progress to the file being resumed from
+        if config.checkpoint.is_none() {
+            config.checkpoint = Some(PathBuf::from(resume_path));
+        }
+        return resume(checkpoint, config);
+    }
+
+    let mut targets = TargetSet::new();
+    let mut target_spec = Vec::new();
+
+    for raw_ip_str in matches.values_of("RANGE").into_iter().flatten() {
+        target_spec.push(String::from(raw_ip_str));
+        match resolver::resolve_target(raw_ip_str, &SystemResolver) {
+            Ok(ip_range) => targets.merge(&ip_range),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+    }
+
+    for hosts_file in matches.values_of("hosts-file").into_iter().flatten() {
+        target_spec.push(format!("hosts from {}", hosts_file));
+        match resolver::read_hosts_file(Path::new(hosts_file)) {
+            Ok(hosts) => targets.merge(&hosts),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+    }
+
+    if matches.is_present("local") {
+        target_spec.push(match matches.value_of("interface") {
+            Some(interface) => format!("local subnets on {}", interface),
+            None => String::from("local subnets"),
+        });
+        let subnets = match interfaces::local_subnets(matches.value_of("interface")) {
+            Ok(subnets) => subnets,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+        for subnet in subnets {
+            // Local subnets may overlap the given range, or each other, but
+            // the target set only keeps each address once
+            match targets.add_subnet(subnet.address, subnet.prefix) {
+                Ok(()) => eprintln!("Adding local subnet {}", subnet),
+                Err(e) => eprintln!("Skipping local subnet {}: {}", subnet, e),
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        println!("No IP addresses to scan");
+        std::process::exit(-1)
+    }
+
+    if let Some(shard_str) = matches.value_of("shard") {
+        let shard: Shard = match shard_str.parse() {
+            Ok(shard) => shard,
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(-1)
+            }
+        };
+        targets = targets.shard(shard);
+        target_spec.push(format!("shard {}", shard));
+        if targets.is_empty() {
+            println!("Shard {} has no IP addresses to scan", shard);
+            std::process::exit(0)
+        }
+        eprintln!("Scanning shard {}", shard);
+    }
+
+    config.target_spec = target_spec.join(", ");
+
+    if matches.is_present("randomize-hosts") {
+        let seed = match matches.value_of("seed") {
+            Some(seed) => match seed.parse() {
+                Ok(seed) => seed,
+                Err(_) => {
+                    println!("The seed must be a positive integer");
+                    std::process::exit(-1)
+                }
+            },
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default(),
+        };
+        eprintln!("Randomizing host order (seed {})", seed);
+        config.seed = Some(seed);
+    }
+
+    if let Some(url) = matches.value_of("webhook") {
+        let mut webhook = Webhook::new(url);
+        if let Some(template_path) = matches.value_of("webhook-template") {
+            webhook.template = match fs::read_to_string(template_path) {
+                Ok(template) => Some(template),
+                Err(e) => {
+                    println!("Couldn't read webhook template {}: {}", template_path, e);
+                    std::process::exit(-1)
+                }
+            };
+        }
+        config.webhook = Some(webhook);
+    }
 
-    let config = Config::new(verbose);
+    if let Some(minutes) = matches.value_of("watch") {
+        let interval = match minutes.parse::<f64>() {
+            Ok(minutes) if minutes > 0.0 => Duration::try_from_secs_f64(minutes * 60.0).ok(),
+            _ => None,
+        };
+        let interval = match interval {
+            Some(interval) => interval,
+            None => {
+                println!("The watch interval must be a positive, finite number of minutes");
+                std::process::exit(-1)
+            }
+        };
+        return watch(&targets, config, interval);
+    }
 
     // main entry point
-    run(ips, config)
+    run(&targets, config)
 }
@@ -5,7 +5,41 @@ use std::net::Ipv4Addr;
 const RESPONSE_BASE_LEN: usize = 57;
 const RESPONSE_NAME_LEN: usize = 15;
 const RESPONSE_NAME_BLOCK_LEN: usize = 18;
+const RESPONSE_UNIT_ID_LEN: usize = 6;
+const MESSENGER_SUFFIX: u8 = 0x03;
 
+const GROUP_NAME_FLAG: u16 = 0x8000;
+const DEREGISTER_FLAG: u16 = 0x1000;
+const CONFLICT_FLAG: u16 = 0x0800;
+const ACTIVE_FLAG: u16 = 0x0400;
+
+/// An entry in the name table of a node status response
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameEntry {
+    pub name: String,
+    pub suffix: u8,
+    pub flags: u16,
+}
+
+impl NameEntry {
+    pub fn is_group(&self) -> bool {
+        self.flags & GROUP_NAME_FLAG != 0
+    }
+
+    pub fn is_in_conflict(&self) -> bool {
+        self.flags & CONFLICT_FLAG != 0
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.flags & ACTIVE_FLAG != 0
+    }
+
+    pub fn is_deregistered(&self) -> bool {
+        self.flags & DEREGISTER_FLAG != 0
+    }
+}
+
+#[derive(Clone)]
 pub struct NetBiosPacket {
     pub ip: Ipv4Addr,
     pub data: [u8; 1024],
@@ -15,10 +49,8 @@ pub struct NetBiosPacket {
 impl Display for NetBiosPacket {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut values = String::new();
-        let mut elem = 1; // print 4 values in a row
         for byte in self.data[0..self.length].iter() {
             values.push_str(&format!("0x{:01$X}, ", byte, 2));
-            elem = elem + 1;
         }
         write!(f, "[{}]", values)
     }
@@ -73,13 +105,74 @@ impl NetBiosPacket {
         self.name()
     }
 
-    pub fn mac_address(&self) -> String {
-        let name_count = (&self.data[RESPONSE_BASE_LEN - 1] & 0xFF) as usize;
+    /// Every name the host has registered, in the order it reported them
+    pub fn names(&self) -> Vec<NameEntry> {
+        let name_count = self.data[RESPONSE_BASE_LEN - 1] as usize;
+        (0..name_count)
+            .map(|n| RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * n)
+            .take_while(|offset| offset + RESPONSE_NAME_BLOCK_LEN <= self.length)
+            .map(|offset| {
+                let block = &self.data[offset..offset + RESPONSE_NAME_BLOCK_LEN];
+                let name = String::from_utf8_lossy(&block[..RESPONSE_NAME_LEN]);
+                NameEntry {
+                    name: String::from(name.trim_end_matches([' ', '\u{0}'])),
+                    suffix: block[RESPONSE_NAME_LEN],
+                    flags: u16::from_be_bytes([block[RESPONSE_NAME_LEN + 1], block[RESPONSE_NAME_LEN + 2]]),
+                }
+            })
+            .collect()
+    }
+
+    /// The workgroup or domain, taken from the first group name with the
+    /// workstation (<00>) suffix
+    pub fn workgroup(&self) -> Option<String> {
+        self.names()
+            .into_iter()
+            .find(|entry| entry.is_group() && entry.suffix == 0x00)
+            .map(|entry| entry.name)
+    }
+
+    /// The unique messenger (<03>) names other than the computer's own.
+    /// Older Windows registers one for the logged-in user.
+    pub fn messenger_names(&self) -> Vec<String> {
+        let computer = self.name();
+        let mut users: Vec<String> = Vec::new();
+        for entry in self.names() {
+            if entry.suffix == MESSENGER_SUFFIX
+                && !entry.is_group()
+                && !entry.name.eq_ignore_ascii_case(&computer)
+                && !users.contains(&entry.name)
+            {
+                users.push(entry.name);
+            }
+        }
+        users
+    }
+
+    /// The statistics block that follows the unit ID (MAC address)
+    pub fn statistics(&self) -> &[u8] {
+        let name_count = self.data[RESPONSE_BASE_LEN - 1] as usize;
+        let offset = RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * name_count + RESPONSE_UNIT_ID_LEN;
+        if offset >= self.length {
+            return &[];
+        }
+        &self.data[offset..self.length]
+    }
+
+    /// The unit ID from the end of the name table, the host's MAC address,
+    /// or all zeros if the reply is too short to hold it
+    pub fn mac_bytes(&self) -> [u8; 6] {
+        let name_count = self.data[RESPONSE_BASE_LEN - 1] as usize;
+        let offset = RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * name_count;
         let mut name_bytes: [u8; 6] = [0; 6];
-        for n in 0..6 {
-            let offset = RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * name_count + n;
-            name_bytes[n] = &self.data[offset] & 0xFF;
+        if offset + RESPONSE_UNIT_ID_LEN <= self.length {
+            name_bytes.copy_from_slice(&self.data[offset..offset + RESPONSE_UNIT_ID_LEN]);
         }
+        name_bytes
+    }
+
+    pub fn mac_address(&self) -> String {
+        let name_bytes = self.mac_bytes();
         format!(
             "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
             name_bytes[0],
@@ -92,8 +185,32 @@ impl NetBiosPacket {
     }
 }
 
+/// Builds replies for tests
+#[cfg(test)]
+pub mod test_support {
+    use super::*;
+
+    /// A node status reply from `ip` with these names (padded name, suffix
+    /// and flags) followed by the MAC address
+    pub fn packet(ip: Ipv4Addr, names: &[(&[u8; 15], u8, u16)], mac: [u8; 6]) -> NetBiosPacket {
+        let mut data = [0u8; 1024];
+        data[RESPONSE_BASE_LEN - 1] = names.len() as u8;
+        for (n, (name, suffix, flags)) in names.iter().enumerate() {
+            let offset = RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * n;
+            data[offset..offset + RESPONSE_NAME_LEN].copy_from_slice(*name);
+            data[offset + RESPONSE_NAME_LEN] = *suffix;
+            data[offset + RESPONSE_NAME_LEN + 1..offset + RESPONSE_NAME_BLOCK_LEN]
+                .copy_from_slice(&flags.to_be_bytes());
+        }
+        let mac_offset = RESPONSE_BASE_LEN + RESPONSE_NAME_BLOCK_LEN * names.len();
+        data[mac_offset..mac_offset + RESPONSE_UNIT_ID_LEN].copy_from_slice(&mac);
+        NetBiosPacket::from(ip, data, mac_offset + RESPONSE_UNIT_ID_LEN)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::test_support::packet;
     use super::*;
 
     #[test]
@@ -117,7 +234,7 @@ mod tests {
         for (i, elem) in packet.iter().enumerate() {
             data[i] = *elem;
         }
-        let _actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175 as usize);
+        let _actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
         assert_eq!(true, true)
     }
 
@@ -143,7 +260,7 @@ mod tests {
             data[i] = *elem;
         }
         let expected = "JACKIEG-WS";
-        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175 as usize);
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
 
         assert_eq!(expected, actual.name());
     }
@@ -170,7 +287,7 @@ mod tests {
             data[i] = *elem;
         }
         let expected = String::from("JACKIEG-WS");
-        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175 as usize);
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
 
         assert_eq!(Some(expected), actual.group());
     }
@@ -197,7 +314,7 @@ mod tests {
             data[i] = *elem;
         }
         let expected = "ALEXK-PC";
-        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175 as usize);
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
 
         assert_eq!(expected, actual.name());
     }
@@ -224,8 +341,72 @@ mod tests {
             data[i] = *elem;
         }
         let expected = "2C:41:38:BA:C3:64";
-        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175 as usize);
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
 
         assert_eq!(expected, actual.mac_address());
     }
+
+    #[test]
+    fn parse_name_table_from_data_correctly() {
+        let mut data = [0u8; 1024];
+        let packet = [
+            0xA2, 0x48, 0x84, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x20, 0x43,
+            0x4B, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41,
+            0x41, 0x41, 0x41, 0x00, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x77,
+            0x04, 0x4A, 0x41, 0x43, 0x4B, 0x49, 0x45, 0x47, 0x2D, 0x57, 0x53, 0x20, 0x20, 0x20,
+            0x20, 0x20, 0x20, 0x44, 0x00, 0x4A, 0x41, 0x43, 0x4B, 0x49, 0x45, 0x47, 0x2D, 0x57,
+            0x53, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x44, 0x00, 0x53, 0x50, 0x49, 0x43, 0x45,
+            0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0xC4, 0x00, 0x53,
+            0x50, 0x49, 0x43, 0x45, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+            0x1E, 0xC4, 0x00, 0x2C, 0x41, 0x38, 0xBA, 0xC3, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        for (i, elem) in packet.iter().enumerate() {
+            data[i] = *elem;
+        }
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 175);
+        let names = actual.names();
+
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[1].name, "JACKIEG-WS");
+        assert_eq!(names[1].suffix, 0x00);
+        assert!(!names[1].is_group());
+        assert_eq!(names[3].suffix, 0x1E);
+        assert!(names[3].is_group() && names[3].is_active());
+        assert_eq!(Some(String::from("SPICE")), actual.workgroup());
+        assert_eq!(actual.statistics().len(), 40);
+    }
+
+    #[test]
+    fn messenger_names_skip_the_computer_name() {
+        let actual = packet(
+            Ipv4Addr::from([127, 0, 0, 1]),
+            &[
+                (b"JACKIEG-WS     ", 0x00, 0x4400),
+                (b"JACKIEG-WS     ", 0x03, 0x4400),
+                (b"JACKIEG        ", 0x03, 0x4400),
+                (b"SPICE          ", 0x03, 0xC400),
+            ],
+            [0; 6],
+        );
+
+        assert_eq!(actual.messenger_names(), vec![String::from("JACKIEG")]);
+    }
+
+    #[test]
+    fn too_many_names_give_a_zero_mac() {
+        let mut data = [0u8; 1024];
+        // 60 names would run past the end of the buffer
+        data[56] = 60;
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 1024);
+        assert_eq!(actual.mac_bytes(), [0; 6]);
+        assert!(actual.statistics().is_empty());
+
+        data[56] = 1;
+        let actual = NetBiosPacket::from(Ipv4Addr::from([127, 0, 0, 1]), data, 57 + 18 + 3);
+        assert_eq!(actual.mac_address(), "00:00:00:00:00:00");
+    }
 }
@@ -0,0 +1,250 @@
+use flate2::read::GzDecoder;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The vendor prefixes built into the binary: a snapshot of the IEEE MA-L,
+/// MA-M and MA-S registries in Wireshark's manuf format, written by
+/// `scripts/update-oui.sh`. Its first line says when it was taken.
+const EMBEDDED: &[u8] = include_bytes!("../data/oui.gz");
+
+static DATABASE: OnceLock<OuiDatabase> = OnceLock::new();
+
+/// Maps MAC address prefixes (OUIs, and the longer MA-M and MA-S blocks) to
+/// the vendor they're assigned to
+#[derive(Debug, Default)]
+pub struct OuiDatabase {
+    vendors: HashMap<(u8, u64), String>,
+    /// The prefix lengths in use
+    lengths: BTreeSet<u8>,
+}
+
+/// How a MAC address was assigned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacKind {
+    /// Assigned by the vendor from its IEEE prefix
+    Universal,
+    /// Set by software, e.g. for virtual machines or randomized addresses,
+    /// so it says nothing about the vendor
+    LocallyAdministered,
+    /// 00:00:00:00:00:00, which Samba and some embedded stacks report
+    Zero,
+}
+
+impl MacKind {
+    pub fn of(mac: [u8; 6]) -> MacKind {
+        if mac == [0; 6] {
+            MacKind::Zero
+        } else if mac[0] & 0x02 != 0 {
+            MacKind::LocallyAdministered
+        } else {
+            MacKind::Universal
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MacKind::Universal => "universal",
+            MacKind::LocallyAdministered => "local",
+            MacKind::Zero => "zero",
+        }
+    }
+}
+
+impl OuiDatabase {
+    /// The prefixes built into the binary
+    pub fn embedded() -> OuiDatabase {
+        let mut contents = String::new();
+        match GzDecoder::new(EMBEDDED).read_to_string(&mut contents) {
+            Ok(_) => OuiDatabase::parse(&contents),
+            Err(_) => OuiDatabase::default(),
+        }
+    }
+
+    /// Reads an IEEE `oui.txt`, `mam.txt` or `oui36.txt` or a Wireshark
+    /// `manuf` file
+    pub fn load(path: &Path) -> io::Result<OuiDatabase> {
+        Ok(OuiDatabase::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn parse(contents: &str) -> OuiDatabase {
+        let mut database = OuiDatabase::default();
+        // The OUI from the last `(hex)` line of an IEEE file
+        let mut oui = String::new();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let block;
+            let entry = if let Some(index) = line.find("(hex)") {
+                // oui.txt: `00-50-56   (hex)		VMware, Inc.`, followed by
+                // a `(base 16)` line that gives the block within the OUI
+                oui = line[..index].trim().replace('-', "");
+                None
+            } else if let Some(index) = line.find("(base 16)") {
+                // `005056` for MA-L, or the block's first and last address
+                // for MA-M and MA-S, e.g. `F1C000-F1CFFF` for a /36
+                let vendor = line[index + 9..].trim();
+                match line[..index].trim().split_once('-') {
+                    Some((first, last)) => {
+                        let shared = first.chars().zip(last.chars()).take_while(|(a, b)| a == b).count();
+                        block = format!("{}{}/{}", oui, first, 24 + 4 * shared);
+                        Some((block.as_str(), vendor))
+                    }
+                    None => Some((line[..index].trim(), vendor)),
+                }
+            } else {
+                // manuf: `00:50:56	VMware	VMware, Inc.`, with an optional /28 or /36
+                let fields: Vec<&str> = line
+                    .split('\t')
+                    .map(str::trim)
+                    .filter(|field| !field.is_empty())
+                    .collect();
+                match fields.as_slice() {
+                    [prefix, .., vendor] => Some((*prefix, *vendor)),
+                    _ => None,
+                }
+            };
+            if let Some((prefix, vendor)) = entry {
+                if let Some(key) = parse_prefix(prefix) {
+                    if !vendor.is_empty() {
+                        database.lengths.insert(key.0);
+                        database.vendors.insert(key, String::from(vendor));
+                    }
+                }
+            }
+        }
+        database
+    }
+
+    /// Adds the entries of `other`, replacing any for the same prefix
+    pub fn merge(&mut self, other: OuiDatabase) {
+        self.lengths.extend(other.lengths);
+        self.vendors.extend(other.vendors);
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vendors.is_empty()
+    }
+
+    /// The vendor of the longest prefix that matches `mac`
+    pub fn vendor(&self, mac: [u8; 6]) -> Option<&str> {
+        let value = mac
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+        self.lengths.iter().rev().find_map(|&bits| {
+            let prefix = value & !((1u64 << (48 - bits)) - 1);
+            self.vendors.get(&(bits, prefix)).map(String::as_str)
+        })
+    }
+}
+
+/// Parses `00:50:56`, `00-50-56`, `005056` or `00:1B:C5:00:00:00/36` into a
+/// prefix length and the prefix as a 48-bit value
+fn parse_prefix(prefix: &str) -> Option<(u8, u64)> {
+    let (digits, bits) = match prefix.find('/') {
+        Some(index) => (
+            &prefix[..index],
+            Some(prefix[index + 1..].parse::<u8>().ok()?),
+        ),
+        None => (prefix, None),
+    };
+    let digits: String = digits
+        .chars()
+        .filter(|c| ![':', '-', '.'].contains(c))
+        .collect();
+    if digits.is_empty() || digits.len() > 12 {
+        return None;
+    }
+    let value = u64::from_str_radix(&digits, 16).ok()? << (48 - digits.len() * 4);
+    let bits = bits.unwrap_or(digits.len() as u8 * 4);
+    if bits == 0 || bits > 48 {
+        return None;
+    }
+    Some((bits, value & !((1u64 << (48 - bits)) - 1)))
+}
+
+/// Replaces the prefixes used by `describe` with `database`. Only takes
+/// effect before the first lookup.
+pub fn use_database(database: OuiDatabase) -> bool {
+    DATABASE.set(database).is_ok()
+}
+
+fn database() -> &'static OuiDatabase {
+    DATABASE.get_or_init(OuiDatabase::embedded)
+}
+
+/// The vendor of a MAC address, or why it can't have one
+pub fn describe(mac: [u8; 6]) -> Option<String> {
+    match MacKind::of(mac) {
+        MacKind::Zero => Some(String::from("(all-zero MAC)")),
+        MacKind::LocallyAdministered => Some(String::from("(locally administered)")),
+        MacKind::Universal => database().vendor(mac).map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_oui_txt_and_manuf_files() {
+        let oui_txt = "OUI/MA-L\t\t\tOrganization\n\
+                       00-50-56   (hex)\t\tVMware, Inc.\n\
+                       005056     (base 16)\t\tVMware, Inc.\n\
+                       \t\t\t\t3401 Hillview Avenue\n";
+        let oui36_txt = "70-B3-D5   (hex)\t\tATX Networks Corp\n\
+                         F1C000-F1CFFF     (base 16)\t\tATX Networks Corp\n";
+        let manuf = "# comment\n\
+                     00:11:32\tSynology\tSynology Incorporated\n\
+                     00:1B:C5:00:00:00/28\tShort\tA Long Name\n\
+                     00:1B:C5:00:10:00/36\tConverging\tConverging Systems Inc.\n";
+        let mut database = OuiDatabase::parse(oui_txt);
+        database.merge(OuiDatabase::parse(manuf));
+        database.merge(OuiDatabase::parse(oui36_txt));
+
+        assert_eq!(database.len(), 5);
+        assert_eq!(
+            database.vendor([0x70, 0xB3, 0xD5, 0xF1, 0xC1, 0x23]),
+            Some("ATX Networks Corp")
+        );
+        assert_eq!(database.vendor([0x70, 0xB3, 0xD5, 0xF1, 0xD1, 0x23]), None);
+        assert_eq!(
+            database.vendor([0x00, 0x50, 0x56, 0x01, 0x02, 0x03]),
+            Some("VMware, Inc.")
+        );
+        assert_eq!(
+            database.vendor([0x00, 0x11, 0x32, 0xAA, 0xBB, 0xCC]),
+            Some("Synology Incorporated")
+        );
+        assert_eq!(
+            database.vendor([0x00, 0x1B, 0xC5, 0x00, 0x10, 0x07]),
+            Some("Converging Systems Inc.")
+        );
+        assert_eq!(
+            database.vendor([0x00, 0x1B, 0xC5, 0x00, 0x20, 0x07]),
+            Some("A Long Name")
+        );
+        assert_eq!(database.vendor([0x00, 0x1B, 0xC6, 0x00, 0x20, 0x07]), None);
+    }
+
+    #[test]
+    fn flags_zero_and_locally_administered_macs() {
+        assert_eq!(MacKind::of([0; 6]), MacKind::Zero);
+        assert_eq!(
+            MacKind::of([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]),
+            MacKind::LocallyAdministered
+        );
+        assert_eq!(
+            describe([0x00, 0x0C, 0x29, 0x12, 0x34, 0x56]).as_deref(),
+            Some("VMware, Inc.")
+        );
+        assert_eq!(describe([0; 6]).as_deref(), Some("(all-zero MAC)"));
+    }
+}
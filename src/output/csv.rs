@@ -0,0 +1,108 @@
+use super::field::Field;
+use super::OutputOptions;
+use crate::nbt_packet::NameEntry;
+use crate::scan_result::{ScanResult, Unanswered};
+use std::io::{self, Write};
+
+/// Writes the selected fields as comma (or tab) separated values with a
+/// header row, either one row per host or one per name table entry
+pub fn write(
+    results: &[ScanResult],
+    unanswered: &[Unanswered],
+    options: &OutputOptions,
+    separator: char,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let header: Vec<String> = options
+        .fields
+        .iter()
+        .map(|field| escape(field.name(), separator))
+        .collect();
+    writeln!(out, "{}", header.join(&separator.to_string()))?;
+
+    for result in results {
+        let names = result.packet.names();
+        // A host without names still gets a row, with the entry fields empty
+        if options.name_rows && !names.is_empty() {
+            for entry in names.iter() {
+                write_row(result, Some(entry), &options.fields, separator, out)?;
+            }
+        } else {
+            write_row(result, None, &options.fields, separator, out)?;
+        }
+    }
+    for unanswered in unanswered {
+        let row: Vec<String> = options
+            .fields
+            .iter()
+            .map(|field| escape(&field.unanswered_value(unanswered).unwrap_or_default(), separator))
+            .collect();
+        writeln!(out, "{}", row.join(&separator.to_string()))?;
+    }
+    Ok(())
+}
+
+fn write_row(
+    result: &ScanResult,
+    entry: Option<&NameEntry>,
+    fields: &[Field],
+    separator: char,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let row: Vec<String> = fields
+        .iter()
+        .map(|field| escape(&field.value(result, entry).unwrap_or_default(), separator))
+        .collect();
+    writeln!(out, "{}", row.join(&separator.to_string()))
+}
+
+/// CSV fields are quoted when they contain the separator, quotes or line
+/// breaks. TSV has no quoting, so those characters are replaced by spaces.
+fn escape(value: &str, separator: char) -> String {
+    if separator == '\t' {
+        return value.replace(['\t', '\n', '\r'], " ");
+    }
+    if value.contains([separator, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    #[test]
+    fn quotes_csv_fields_only_when_needed() {
+        assert_eq!(escape("JACKIEG-WS", ','), "JACKIEG-WS");
+        assert_eq!(escape("ACME, Inc.", ','), "\"ACME, Inc.\"");
+        assert_eq!(escape("12\" rack", ','), "\"12\"\" rack\"");
+        assert_eq!(escape("a\tb", '\t'), "a b");
+    }
+
+    #[test]
+    fn hosts_without_names_get_a_name_row() {
+        let packet = packet(
+            Ipv4Addr::new(10, 1, 2, 3),
+            &[],
+            [0x00, 0x0C, 0x29, 0x12, 0x34, 0x56],
+        );
+        let results = [ScanResult::new(packet, Duration::from_millis(1))];
+        let options = OutputOptions {
+            name_rows: true,
+            fields: vec![Field::Ip, Field::Mac, Field::Entry, Field::Suffix, Field::Type],
+            ..OutputOptions::default()
+        };
+
+        let mut out = Vec::new();
+        write(&results, &[], &options, ',', &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ip,mac,entry,suffix,type\n10.1.2.3,00:0C:29:12:34:56,,,\n"
+        );
+    }
+}
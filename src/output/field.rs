@@ -0,0 +1,145 @@
+use super::OutputError;
+use crate::nbt_packet::NameEntry;
+use crate::roles::Role;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use std::str::FromStr;
+
+/// A value that can be picked out of a scan result for tabular output.
+///
+/// The name table fields (`entry`, `suffix`, `type`) refer to a single name
+/// entry, so they're only filled in when writing one row per name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Ip,
+    Hostname,
+    State,
+    Name,
+    Workgroup,
+    Mac,
+    Vendor,
+    Fingerprint,
+    Roles,
+    Users,
+    Rtt,
+    Attempts,
+    Entry,
+    Suffix,
+    Type,
+}
+
+pub const DEFAULT_FIELDS: [Field; 5] = [Field::Ip, Field::Name, Field::Workgroup, Field::Mac, Field::Rtt];
+
+impl Field {
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Ip => "ip",
+            Field::Hostname => "hostname",
+            Field::State => "state",
+            Field::Name => "name",
+            Field::Workgroup => "workgroup",
+            Field::Mac => "mac",
+            Field::Vendor => "vendor",
+            Field::Fingerprint => "fingerprint",
+            Field::Roles => "roles",
+            Field::Users => "users",
+            Field::Rtt => "rtt",
+            Field::Attempts => "attempts",
+            Field::Entry => "entry",
+            Field::Suffix => "suffix",
+            Field::Type => "type",
+        }
+    }
+
+    /// Whether the field refers to a single name entry rather than the host
+    pub fn is_entry_field(self) -> bool {
+        matches!(self, Field::Entry | Field::Suffix | Field::Type)
+    }
+
+    /// The field's value for a host, or for one of its name entries.
+    /// Missing values are `None`.
+    pub fn value(self, result: &ScanResult, entry: Option<&NameEntry>) -> Option<String> {
+        match self {
+            Field::Ip => Some(result.ip.to_string()),
+            Field::Hostname => result.hostname.clone(),
+            Field::State => Some(String::from(HostState::Responding.name())),
+            Field::Name => Some(result.packet.name()),
+            Field::Workgroup => result.packet.workgroup(),
+            Field::Mac => Some(result.packet.mac_address()),
+            Field::Vendor => result.vendor(),
+            Field::Fingerprint => result.fingerprint().map(|fingerprint| fingerprint.to_string()),
+            Field::Roles => {
+                let roles: Vec<&str> = result.roles().into_iter().map(Role::name).collect();
+                Some(roles.join(","))
+            }
+            Field::Users => result.users.as_ref().map(|users| users.join(",")),
+            Field::Rtt => Some(format!("{:.3}", result.rtt.as_secs_f64() * 1000.0)),
+            Field::Attempts => Some(result.attempts.to_string()),
+            Field::Entry => entry.map(|entry| entry.name.clone()),
+            Field::Suffix => entry.map(|entry| format!("{:02X}", entry.suffix)),
+            Field::Type => entry.map(|entry| {
+                String::from(if entry.is_group() { "GROUP" } else { "UNIQUE" })
+            }),
+        }
+    }
+
+    /// The field's value for a target that didn't answer with a name
+    /// table, which only has an address, a hostname and a state
+    pub fn unanswered_value(self, unanswered: &Unanswered) -> Option<String> {
+        match self {
+            Field::Ip => Some(unanswered.ip.to_string()),
+            Field::Hostname => unanswered.hostname.clone(),
+            Field::State => Some(String::from(unanswered.state.name())),
+            _ => None,
+        }
+    }
+
+    /// Parses a comma separated list of field names, e.g. `ip,name,mac`
+    pub fn parse_list(fields: &str) -> Result<Vec<Field>, OutputError> {
+        fields.split(',').map(|field| field.trim().parse()).collect()
+    }
+}
+
+impl FromStr for Field {
+    type Err = OutputError;
+
+    fn from_str(field: &str) -> Result<Field, OutputError> {
+        let fields = [
+            Field::Ip,
+            Field::Hostname,
+            Field::State,
+            Field::Name,
+            Field::Workgroup,
+            Field::Mac,
+            Field::Vendor,
+            Field::Fingerprint,
+            Field::Roles,
+            Field::Users,
+            Field::Rtt,
+            Field::Attempts,
+            Field::Entry,
+            Field::Suffix,
+            Field::Type,
+        ];
+        fields
+            .iter()
+            .find(|candidate| candidate.name() == field)
+            .cloned()
+            .ok_or_else(|| OutputError::UnknownFieldError(String::from(field)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn parses_a_column_list() {
+        let fields = Field::parse_list("ip, name,mac").unwrap();
+        assert_eq!(fields, vec![Field::Ip, Field::Name, Field::Mac]);
+        assert_matches!(
+            Field::parse_list("ip,owner"),
+            Err(OutputError::UnknownFieldError(ref field)) if field == "owner"
+        );
+    }
+}
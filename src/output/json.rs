@@ -0,0 +1,171 @@
+use crate::fingerprint::Fingerprint;
+use crate::hex::to_hex;
+use crate::nbt_packet::NameEntry;
+use crate::roles::Role;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use crate::watch::Event;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::net::Ipv4Addr;
+
+#[derive(Serialize)]
+struct HostRecord<'a> {
+    ip: Ipv4Addr,
+    hostname: Option<&'a str>,
+    state: HostState,
+    name: String,
+    workgroup: Option<String>,
+    mac: String,
+    vendor: Option<String>,
+    mac_type: &'static str,
+    fingerprint: Option<Fingerprint>,
+    roles: Vec<Role>,
+    /// Only there when users were asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    users: Option<&'a [String]>,
+    rtt_ms: f64,
+    attempts: u32,
+    names: Vec<NameRecord>,
+    statistics: String,
+}
+
+/// A target that didn't answer with a name table
+#[derive(Serialize)]
+struct UnansweredRecord<'a> {
+    ip: Ipv4Addr,
+    hostname: Option<&'a str>,
+    state: HostState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl<'a> UnansweredRecord<'a> {
+    fn from(unanswered: &'a Unanswered) -> UnansweredRecord<'a> {
+        UnansweredRecord {
+            ip: unanswered.ip,
+            hostname: unanswered.hostname.as_deref(),
+            state: unanswered.state,
+            error: unanswered.error.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Record<'a> {
+    Host(Box<HostRecord<'a>>),
+    Unanswered(UnansweredRecord<'a>),
+}
+
+#[derive(Serialize)]
+struct NameRecord {
+    name: String,
+    suffix: String,
+    group: bool,
+    flags: u16,
+}
+
+impl<'a> HostRecord<'a> {
+    fn from(result: &'a ScanResult) -> HostRecord<'a> {
+        HostRecord {
+            ip: result.ip,
+            hostname: result.hostname.as_deref(),
+            state: HostState::Responding,
+            name: result.packet.name(),
+            workgroup: result.packet.workgroup(),
+            mac: result.packet.mac_address(),
+            vendor: result.vendor(),
+            mac_type: result.mac_kind().name(),
+            fingerprint: result.fingerprint(),
+            roles: result.roles(),
+            users: result.users.as_deref(),
+            rtt_ms: result.rtt.as_micros() as f64 / 1000.0,
+            attempts: result.attempts,
+            names: result.packet.names().iter().map(NameRecord::from).collect(),
+            statistics: to_hex(result.packet.statistics()),
+        }
+    }
+}
+
+impl NameRecord {
+    fn from(entry: &NameEntry) -> NameRecord {
+        NameRecord {
+            name: entry.name.clone(),
+            suffix: format!("{:02X}", entry.suffix),
+            group: entry.is_group(),
+            flags: entry.flags,
+        }
+    }
+}
+
+pub fn write(results: &[ScanResult], unanswered: &[Unanswered], out: &mut dyn Write) -> io::Result<()> {
+    let records: Vec<Record> = results
+        .iter()
+        .map(|result| Record::Host(Box::new(HostRecord::from(result))))
+        .chain(unanswered.iter().map(|unanswered| Record::Unanswered(UnansweredRecord::from(unanswered))))
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &records)?;
+    writeln!(out)
+}
+
+pub fn write_line(result: &ScanResult, out: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &HostRecord::from(result))?;
+    writeln!(out)?;
+    out.flush()
+}
+
+pub fn write_unanswered_line(unanswered: &Unanswered, out: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &UnansweredRecord::from(unanswered))?;
+    writeln!(out)
+}
+
+pub fn write_event(event: &Event, out: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, event)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::time::Duration;
+
+    #[test]
+    fn writes_one_object_per_line() {
+        let packet = packet(
+            Ipv4Addr::new(10, 1, 2, 3),
+            &[(b"SPICE          ", 0x00, 0xC400)],
+            [0x2C, 0x41, 0x38, 0xBA, 0xC3, 0x64],
+        );
+        let result = ScanResult::new(packet, Duration::from_micros(1500));
+
+        let mut out = Vec::new();
+        write_line(&result, &mut out).unwrap();
+        let line = String::from_utf8(out).unwrap();
+
+        assert!(line.ends_with("}\n"));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["ip"], "10.1.2.3");
+        assert_eq!(value["workgroup"], "SPICE");
+        assert_eq!(value["rtt_ms"], 1.5);
+        assert_eq!(value["names"][0]["suffix"], "00");
+        assert_eq!(value["names"][0]["group"], true);
+        assert_eq!(value["state"], "responding");
+        assert_eq!(value["attempts"], 1);
+    }
+
+    #[test]
+    fn writes_the_state_of_unanswered_targets() {
+        let closed = Unanswered {
+            ip: Ipv4Addr::new(10, 1, 2, 4),
+            hostname: None,
+            state: HostState::Closed,
+            error: None,
+        };
+        let mut out = Vec::new();
+        write_unanswered_line(&closed, &mut out).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert_eq!(line, "{\"ip\":\"10.1.2.4\",\"hostname\":null,\"state\":\"closed\"}\n");
+    }
+}
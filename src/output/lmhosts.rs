@@ -0,0 +1,154 @@
+use crate::scan_result::ScanResult;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::Ipv4Addr;
+
+const DOMAIN_CONTROLLER_SUFFIX: u8 = 0x1C;
+const FILE_SERVER_SUFFIX: u8 = 0x20;
+
+/// Writes an LMHOSTS file mapping each host's NetBIOS name to its address.
+/// Hosts advertising a <1C> domain controller name are preloaded and tagged
+/// with their domain.
+pub fn write_lmhosts(results: &[ScanResult], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "# LMHOSTS generated by nbtscanner")?;
+    write_entries(results, out, |result, name| {
+        let domain = result
+            .packet
+            .names()
+            .into_iter()
+            .find(|entry| entry.is_group() && entry.suffix == DOMAIN_CONTROLLER_SUFFIX);
+        let name = match lmhosts_name(name) {
+            Some(name) => name,
+            None => return format!("# can't be written in LMHOSTS: {:<16}{}", result.ip, name),
+        };
+        match domain {
+            Some(domain) => format!("{:<16}{:<16}#PRE #DOM:{}", result.ip, name, domain.name),
+            None => format!("{:<16}{}", result.ip, name),
+        }
+    })
+}
+
+/// The name as LMHOSTS needs it written. Names with spaces, `#` or control
+/// characters have to be quoted, and a quoted name stands for a single
+/// suffix, given as `\0xNN` after padding to 15 characters. The file server
+/// suffix is the one used to connect to the host. Names containing quotes
+/// can't be written at all.
+fn lmhosts_name(name: &str) -> Option<String> {
+    if name.contains('"') {
+        return None;
+    }
+    if name.chars().all(|c| c.is_ascii_graphic() && c != '#') {
+        return Some(String::from(name));
+    }
+    Some(format!("\"{:<15}\\0x{:02x}\"", name, FILE_SERVER_SUFFIX))
+}
+
+/// Writes an /etc/hosts style file mapping each address to the host's
+/// lowercased NetBIOS name
+pub fn write_hosts(results: &[ScanResult], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "# hosts generated by nbtscanner")?;
+    write_entries(results, out, |result, name| {
+        let hostname = name.to_lowercase();
+        if is_hostname(&hostname) {
+            format!("{:<16}{}", result.ip, hostname)
+        } else {
+            format!("# not a valid hostname: {:<16}{}", result.ip, hostname)
+        }
+    })
+}
+
+/// Whether the name is a valid host name label under RFC 952 and RFC 1123:
+/// letters, digits and hyphens, not starting or ending with a hyphen
+fn is_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Writes one line per host. A name already taken by an earlier host is
+/// commented out, and names the host reports as in conflict are noted above
+/// its line.
+fn write_entries<F>(results: &[ScanResult], out: &mut dyn Write, line: F) -> io::Result<()>
+where
+    F: Fn(&ScanResult, &str) -> String,
+{
+    let mut seen: HashMap<String, Ipv4Addr> = HashMap::new();
+    for result in results {
+        let name = result.packet.name();
+        if name.is_empty() {
+            continue;
+        }
+        for entry in result.packet.names().iter().filter(|entry| entry.is_in_conflict()) {
+            writeln!(
+                out,
+                "# {}<{:02X}> is in conflict on {}",
+                entry.name, entry.suffix, result.ip
+            )?;
+        }
+        match seen.get(&name.to_uppercase()) {
+            Some(first) => writeln!(out, "# duplicate of {}: {}", first, line(result, &name))?,
+            None => {
+                seen.insert(name.to_uppercase(), result.ip);
+                writeln!(out, "{}", line(result, &name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::time::Duration;
+
+    fn result(ip: Ipv4Addr, names: &[(&[u8; 15], u8, u16)]) -> ScanResult {
+        ScanResult::new(packet(ip, names, [0; 6]), Duration::from_millis(2))
+    }
+
+    #[test]
+    fn tags_domain_controllers_and_comments_out_duplicates() {
+        let dc = [
+            (b"DC01           ", 0x00, 0x0400),
+            (b"CORP           ", 0x1C, 0x8400),
+        ];
+        let results = vec![
+            result(Ipv4Addr::new(10, 1, 2, 3), &dc),
+            result(Ipv4Addr::new(10, 1, 2, 4), &dc),
+        ];
+
+        let mut out = Vec::new();
+        write_lmhosts(&results, &mut out).unwrap();
+        let lines: Vec<String> = String::from_utf8(out).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines[1], "10.1.2.3        DC01            #PRE #DOM:CORP");
+        assert_eq!(lines[2], "# duplicate of 10.1.2.3: 10.1.2.4        DC01            #PRE #DOM:CORP");
+
+        let mut out = Vec::new();
+        write_hosts(&results[..1], &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().nth(1), Some("10.1.2.3        dc01"));
+    }
+
+    #[test]
+    fn quotes_or_comments_out_awkward_names() {
+        let lab = [(b"LAB PC#1       ", 0x00, 0x0400)];
+        let results = vec![result(Ipv4Addr::new(10, 1, 2, 5), &lab)];
+
+        let mut out = Vec::new();
+        write_lmhosts(&results, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().nth(1),
+            Some("10.1.2.5        \"LAB PC#1       \\0x20\"")
+        );
+
+        let mut out = Vec::new();
+        write_hosts(&results, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap().lines().nth(1),
+            Some("# not a valid hostname: 10.1.2.5        lab pc#1")
+        );
+        assert!(is_hostname("ws-042"));
+        assert!(!is_hostname("file_srv"));
+    }
+}
@@ -0,0 +1,199 @@
+use self::OutputError::*;
+use crate::scan_result::{ScanResult, Unanswered};
+use crate::timestamp::format_utc;
+use crate::watch::Event;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+mod csv;
+mod field;
+mod json;
+mod lmhosts;
+mod names;
+mod summary;
+mod table;
+mod template;
+mod xml;
+
+pub use self::field::Field;
+pub use self::template::Template;
+
+/// How scan results are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// The fixed-width table, printed once the scan is done
+    Table,
+    /// A JSON array of every host, printed once the scan is done
+    Json,
+    /// One JSON object per line, printed as each host answers
+    Ndjson,
+    /// Comma separated values with a header row
+    Csv,
+    /// Tab separated values with a header row
+    Tsv,
+    /// nmap's XML output format
+    Xml,
+    /// Every host's full name table, like `nbtstat -A`
+    Names,
+    /// An LMHOSTS file
+    Lmhosts,
+    /// An /etc/hosts style file
+    Hosts,
+    /// Host counts, domain controllers, master browsers and name conflicts
+    /// for each workgroup
+    Summary,
+}
+
+impl OutputFormat {
+    /// Whether results are written as they arrive rather than at the end
+    pub fn is_streaming(self) -> bool {
+        self == OutputFormat::Ndjson
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = OutputError;
+
+    fn from_str(format: &str) -> Result<OutputFormat, OutputError> {
+        match format {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            "xml" => Ok(OutputFormat::Xml),
+            "names" => Ok(OutputFormat::Names),
+            "lmhosts" => Ok(OutputFormat::Lmhosts),
+            "hosts" => Ok(OutputFormat::Hosts),
+            "summary" => Ok(OutputFormat::Summary),
+            _ => Err(UnknownFormatError(String::from(format))),
+        }
+    }
+}
+
+/// Everything that controls how results are written
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// The columns written by the CSV and TSV formats
+    pub fields: Vec<Field>,
+    /// Write one row per name table entry instead of one per host
+    pub name_rows: bool,
+    /// A user-defined line format, used instead of `format` when given
+    pub template: Option<Template>,
+    /// The command line the scan was started with, recorded by formats that
+    /// keep run metadata
+    pub command_line: String,
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions {
+            format: OutputFormat::Table,
+            fields: field::DEFAULT_FIELDS.to_vec(),
+            name_rows: false,
+            template: None,
+            command_line: String::new(),
+        }
+    }
+}
+
+/// Facts about the scan as a whole, for formats that record them
+pub struct ScanInfo {
+    pub command_line: String,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// How many targets were probed
+    pub targets: u64,
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    UnknownFormatError(String),
+    UnknownFieldError(String),
+    TemplateError(String),
+}
+
+impl Error for OutputError {}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            UnknownFormatError(format) => write!(
+                f,
+                "Unknown output format '{}', expected table, json, ndjson, csv, tsv, xml, names, lmhosts, hosts or summary",
+                format
+            ),
+            UnknownFieldError(field) => write!(f, "Unknown column '{}'", field),
+            TemplateError(message) => write!(f, "Invalid format template: {}", message),
+        }
+    }
+}
+
+/// Writes one result as soon as it arrives, for formats that stream
+pub(crate) fn write_result(
+    options: &OutputOptions,
+    result: &ScanResult,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    match options.format {
+        OutputFormat::Ndjson => json::write_line(result, out),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the results of the whole scan, for formats that don't stream
+///
+/// `unanswered` holds the targets that are closed or couldn't be probed, to
+/// show as well. Templates and the LMHOSTS and hosts formats leave them out
+/// since they're about names.
+pub(crate) fn write_results(
+    options: &OutputOptions,
+    results: &[ScanResult],
+    unanswered: &[Unanswered],
+    info: &ScanInfo,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    if let Some(ref template) = options.template {
+        return template.write(results, out);
+    }
+    match options.format {
+        OutputFormat::Table => table::write(results, unanswered, out),
+        OutputFormat::Json => json::write(results, unanswered, out),
+        // The hosts that answered were streamed as they came in
+        OutputFormat::Ndjson => unanswered
+            .iter()
+            .try_for_each(|unanswered| json::write_unanswered_line(unanswered, out)),
+        OutputFormat::Csv => csv::write(results, unanswered, options, ',', out),
+        OutputFormat::Tsv => csv::write(results, unanswered, options, '\t', out),
+        OutputFormat::Xml => xml::write(results, unanswered, info, out),
+        OutputFormat::Names => names::write(results, unanswered, out),
+        OutputFormat::Lmhosts => lmhosts::write_lmhosts(results, out),
+        OutputFormat::Hosts => lmhosts::write_hosts(results, out),
+        OutputFormat::Summary => summary::write(results, unanswered, out),
+    }
+}
+
+/// Writes the targets that didn't answer, one address per line
+pub(crate) fn write_silent(addresses: &[Ipv4Addr], out: &mut dyn Write) -> io::Result<()> {
+    for address in addresses {
+        writeln!(out, "{}", address)?;
+    }
+    Ok(())
+}
+
+/// Writes a change seen while watching, as NDJSON with the ndjson format and
+/// as a line of text otherwise
+pub(crate) fn write_event(options: &OutputOptions, event: &Event, out: &mut dyn Write) -> io::Result<()> {
+    match options.format {
+        OutputFormat::Ndjson => json::write_event(event, out),
+        _ => {
+            writeln!(out, "{}  {}", format_utc(event.time), event.change)?;
+            out.flush()
+        }
+    }
+}
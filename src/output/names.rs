@@ -0,0 +1,108 @@
+use crate::nbt_packet::NameEntry;
+use crate::roles::Role;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use std::io::{self, Write};
+
+/// Writes every host's full name table, laid out like `nbtstat -A`
+pub fn write(results: &[ScanResult], unanswered: &[Unanswered], out: &mut dyn Write) -> io::Result<()> {
+    for result in results {
+        match result.hostname {
+            Some(ref hostname) => writeln!(out, "{} ({})", result.ip, hostname)?,
+            None => writeln!(out, "{}", result.ip)?,
+        }
+        writeln!(out)?;
+        writeln!(out, "    NetBIOS Remote Machine Name Table")?;
+        writeln!(out)?;
+        writeln!(out, "       Name               Type         Status")?;
+        writeln!(out, "    ---------------------------------------------")?;
+        for entry in result.packet.names().iter() {
+            writeln!(
+                out,
+                "    {:<15}<{:02X}>  {:<11} {}",
+                printable(&entry.name),
+                entry.suffix,
+                if entry.is_group() { "GROUP" } else { "UNIQUE" },
+                status(entry)
+            )?;
+        }
+        writeln!(out)?;
+        write!(out, "    MAC Address = {}", result.packet.mac_address().replace(':', "-"))?;
+        match result.vendor() {
+            Some(vendor) => writeln!(out, " {}", vendor)?,
+            None => writeln!(out)?,
+        }
+        if let Some(fingerprint) = result.fingerprint() {
+            writeln!(out, "    Fingerprint = {}", fingerprint)?;
+        }
+        let roles: Vec<&str> = result.roles().into_iter().map(Role::name).collect();
+        if !roles.is_empty() {
+            writeln!(out, "    Roles = {}", roles.join(", "))?;
+        }
+        if let Some(ref users) = result.users {
+            if !users.is_empty() {
+                writeln!(out, "    Users = {}", users.join(", "))?;
+            }
+        }
+        writeln!(out)?;
+    }
+    for unanswered in unanswered {
+        match unanswered.hostname {
+            Some(ref hostname) => writeln!(out, "{} ({})", unanswered.ip, hostname)?,
+            None => writeln!(out, "{}", unanswered.ip)?,
+        }
+        writeln!(out)?;
+        match (unanswered.state, &unanswered.error) {
+            (HostState::Error, Some(error)) => writeln!(out, "    Couldn't be probed: {}", error)?,
+            (HostState::Closed, _) => writeln!(out, "    Host is up, but NetBIOS is closed")?,
+            (state, _) => writeln!(out, "    NetBIOS {}", state.name())?,
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn status(entry: &NameEntry) -> &'static str {
+    if entry.is_in_conflict() {
+        "Conflict"
+    } else if entry.is_deregistered() {
+        "Deregistered"
+    } else {
+        "Registered"
+    }
+}
+
+/// Names like `\x01\x02__MSBROWSE__\x02` contain control characters, which
+/// nbtstat shows as dots
+fn printable(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_control() { '.' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    #[test]
+    fn lists_every_name_with_its_type_and_status() {
+        let packet = packet(
+            Ipv4Addr::new(10, 1, 2, 3),
+            &[
+                (b"FILESERVER     ", 0x20, 0x0400),
+                (b"\x01\x02__MSBROWSE__\x02", 0x01, 0x8C00),
+            ],
+            [0x2C, 0x41, 0x38, 0xBA, 0xC3, 0x64],
+        );
+
+        let mut out = Vec::new();
+        write(&[ScanResult::new(packet, Duration::from_millis(2))], &[], &mut out).unwrap();
+        let output = String::from_utf8(out).unwrap();
+
+        assert!(output.contains("    FILESERVER     <20>  UNIQUE      Registered\n"));
+        assert!(output.contains("    ..__MSBROWSE__.<01>  GROUP       Conflict\n"));
+        assert!(output.contains("    MAC Address = 2C-41-38-BA-C3-64\n"));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::roles::Role;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// The hosts that answered in one workgroup or domain
+struct Workgroup<'a> {
+    hosts: Vec<&'a ScanResult>,
+    domain_controllers: Vec<&'a ScanResult>,
+    master_browsers: Vec<&'a ScanResult>,
+    conflicts: Vec<&'a ScanResult>,
+}
+
+/// Writes a report grouped by workgroup or domain: how many hosts each has,
+/// its domain controllers, master browsers and hosts with name conflicts.
+/// Hosts that didn't report a workgroup are listed at the end, followed by
+/// the ones with NetBIOS closed.
+pub fn write(results: &[ScanResult], unanswered: &[Unanswered], out: &mut dyn Write) -> io::Result<()> {
+    let mut workgroups: BTreeMap<String, Workgroup> = BTreeMap::new();
+    let mut ungrouped = Vec::new();
+    for result in results {
+        let name = match result.packet.workgroup() {
+            Some(name) => name,
+            None => {
+                ungrouped.push(result);
+                continue;
+            }
+        };
+        let workgroup = workgroups.entry(name).or_insert_with(|| Workgroup {
+            hosts: Vec::new(),
+            domain_controllers: Vec::new(),
+            master_browsers: Vec::new(),
+            conflicts: Vec::new(),
+        });
+        let roles = result.roles();
+        workgroup.hosts.push(result);
+        if roles.contains(&Role::DomainController) {
+            workgroup.domain_controllers.push(result);
+        }
+        if roles.contains(&Role::DomainMasterBrowser) || roles.contains(&Role::MasterBrowser) {
+            workgroup.master_browsers.push(result);
+        }
+        if result
+            .packet
+            .names()
+            .iter()
+            .any(|entry| entry.is_in_conflict())
+        {
+            workgroup.conflicts.push(result);
+        }
+    }
+
+    // The biggest workgroups first
+    let mut sorted: Vec<(&String, &Workgroup)> = workgroups.iter().collect();
+    sorted.sort_by_key(|(_, workgroup)| std::cmp::Reverse(workgroup.hosts.len()));
+
+    for (name, workgroup) in sorted.iter() {
+        writeln!(out, "{} ({})", name, hosts(workgroup.hosts.len()))?;
+        writeln!(
+            out,
+            "    Domain controllers  {}",
+            list(&workgroup.domain_controllers)
+        )?;
+        writeln!(
+            out,
+            "    Master browsers     {}",
+            list(&workgroup.master_browsers)
+        )?;
+        writeln!(
+            out,
+            "    Name conflicts      {}",
+            list(&workgroup.conflicts)
+        )?;
+        writeln!(out)?;
+    }
+    if !ungrouped.is_empty() {
+        writeln!(out, "No workgroup ({})", hosts(ungrouped.len()))?;
+        for result in ungrouped.iter() {
+            writeln!(out, "    {}", describe(result))?;
+        }
+        writeln!(out)?;
+    }
+    let closed: Vec<&Unanswered> = unanswered
+        .iter()
+        .filter(|unanswered| unanswered.state == HostState::Closed)
+        .collect();
+    if !closed.is_empty() {
+        writeln!(out, "NetBIOS closed ({})", hosts(closed.len()))?;
+        for unanswered in closed.iter() {
+            writeln!(out, "    {}", unanswered.ip)?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(
+        out,
+        "{} in {} workgroup{}",
+        hosts(results.len()),
+        workgroups.len(),
+        if workgroups.len() == 1 { "" } else { "s" }
+    )
+}
+
+fn hosts(count: usize) -> String {
+    format!("{} host{}", count, if count == 1 { "" } else { "s" })
+}
+
+fn describe(result: &ScanResult) -> String {
+    format!("{} {}", result.packet.name(), result.ip)
+}
+
+fn list(results: &[&ScanResult]) -> String {
+    if results.is_empty() {
+        return String::from("none");
+    }
+    let described: Vec<String> = results.iter().map(|result| describe(result)).collect();
+    described.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn result(last_octet: u8, names: &[(&[u8; 15], u8, u16)]) -> ScanResult {
+        let packet = packet(Ipv4Addr::new(10, 0, 0, last_octet), names, [0; 6]);
+        ScanResult::new(packet, Duration::from_millis(2))
+    }
+
+    #[test]
+    fn groups_hosts_by_workgroup() {
+        let results = vec![
+            result(
+                5,
+                &[
+                    (b"DC01           ", 0x00, 0x0400),
+                    (b"CORP           ", 0x00, 0x8400),
+                    (b"CORP           ", 0x1C, 0x8400),
+                ],
+            ),
+            result(
+                6,
+                &[
+                    (b"PC-14          ", 0x00, 0x0C00),
+                    (b"CORP           ", 0x00, 0x8400),
+                ],
+            ),
+            result(
+                7,
+                &[
+                    (b"LAB1           ", 0x00, 0x0400),
+                    (b"LAB            ", 0x00, 0x8400),
+                    (b"LAB1           ", 0x1D, 0x0400),
+                ],
+            ),
+            result(99, &[(b"NPI3F2A1C      ", 0x00, 0x0400)]),
+        ];
+        let mut out = Vec::new();
+        write(&results, &[], &mut out).unwrap();
+
+        let expected = "CORP (2 hosts)\n\
+                        \x20   Domain controllers  DC01 10.0.0.5\n\
+                        \x20   Master browsers     none\n\
+                        \x20   Name conflicts      PC-14 10.0.0.6\n\
+                        \n\
+                        LAB (1 host)\n\
+                        \x20   Domain controllers  none\n\
+                        \x20   Master browsers     LAB1 10.0.0.7\n\
+                        \x20   Name conflicts      none\n\
+                        \n\
+                        No workgroup (1 host)\n\
+                        \x20   NPI3F2A1C 10.0.0.99\n\
+                        \n\
+                        4 hosts in 2 workgroups\n";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::resolver;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use std::io::{self, Write};
+
+pub fn write(results: &[ScanResult], unanswered: &[Unanswered], out: &mut dyn Write) -> io::Result<()> {
+    let has_hostnames = results.iter().any(|result| result.hostname.is_some())
+        || unanswered.iter().any(|unanswered| unanswered.hostname.is_some());
+
+    for result in results {
+        if has_hostnames {
+            let hostname = result.hostname.as_deref().unwrap_or("");
+            let mismatch =
+                !hostname.is_empty() && !resolver::names_agree(hostname, &result.packet.name());
+            let line = format!(
+                "{ip:<16}{hostname:<32}{group_and_name:<32}{mac:<18}{mismatch}",
+                ip = format!("{}", result.ip),
+                hostname = hostname,
+                group_and_name = result.packet.group_and_name(),
+                mac = result.packet.mac_address(),
+                mismatch = if mismatch { "NetBIOS name differs" } else { "" }
+            );
+            writeln!(out, "{}", line.trim_end())?;
+        } else {
+            writeln!(
+                out,
+                "{ip:<16}{group_and_name:<32}{mac:<15}",
+                ip = format!("{}", result.ip),
+                group_and_name = result.packet.group_and_name(),
+                mac = result.packet.mac_address()
+            )?;
+        }
+    }
+    for unanswered in unanswered {
+        let state = match (unanswered.state, &unanswered.error) {
+            (HostState::Error, Some(error)) => format!("(error: {})", error),
+            (state, _) => format!("(NetBIOS {})", state.name()),
+        };
+        let line = if has_hostnames {
+            format!(
+                "{ip:<16}{hostname:<32}{state}",
+                ip = format!("{}", unanswered.ip),
+                hostname = unanswered.hostname.as_deref().unwrap_or(""),
+                state = state
+            )
+        } else {
+            format!("{ip:<16}{state}", ip = format!("{}", unanswered.ip), state = state)
+        };
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
\ No newline at end of file
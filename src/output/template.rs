@@ -0,0 +1,319 @@
+use super::field::Field;
+use super::OutputError;
+use crate::nbt_packet::NameEntry;
+use crate::scan_result::ScanResult;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// A user-defined line format, e.g. `{ip:<16}{entry}<{suffix}>{?hostname} ({hostname}){/hostname}`.
+///
+/// - `{field}` is replaced by a field's value, `{field:<16}` / `{field:>16}`
+///   pads it to a width, `{field:.8}` cuts it off, and `{field|-}` gives the
+///   text to use when the value is missing
+/// - `{?field}...{/field}` is only written when the field has a value, and
+///   `{^field}...{/field}` only when it doesn't
+/// - `{#names}...{/names}` is repeated for every name table entry, and the
+///   `entry`, `suffix` and `type` fields refer to that entry inside it.
+///   `name` stays the host's computer name everywhere
+/// - `\t`, `\n` and `\\` are escapes, `{{` and `}}` are literal braces
+///
+/// A template is written once per host, followed by a newline. If it uses the
+/// name entry fields outside of a `{#names}` section it's written once per
+/// name table entry instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Value(Placeholder),
+    Names(Vec<Part>),
+    IfPresent(Field, Vec<Part>),
+    IfMissing(Field, Vec<Part>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Placeholder {
+    field: Field,
+    align_right: bool,
+    width: usize,
+    max_width: Option<usize>,
+    missing: String,
+}
+
+impl Template {
+    pub fn write(&self, results: &[ScanResult], out: &mut dyn Write) -> io::Result<()> {
+        for result in results {
+            if uses_entry(&self.parts) {
+                for entry in result.packet.names().iter() {
+                    writeln!(out, "{}", self.render(result, Some(entry)))?;
+                }
+            } else {
+                writeln!(out, "{}", self.render(result, None))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, result: &ScanResult, entry: Option<&NameEntry>) -> String {
+        let mut line = String::new();
+        render_parts(&self.parts, result, entry, &mut line);
+        line
+    }
+}
+
+fn render_parts(parts: &[Part], result: &ScanResult, entry: Option<&NameEntry>, line: &mut String) {
+    for part in parts {
+        match part {
+            Part::Text(text) => line.push_str(text),
+            Part::Value(placeholder) => line.push_str(&placeholder.render(result, entry)),
+            Part::Names(parts) => {
+                for entry in result.packet.names().iter() {
+                    render_parts(parts, result, Some(entry), line);
+                }
+            }
+            Part::IfPresent(field, parts) => {
+                if field.value(result, entry).is_some() {
+                    render_parts(parts, result, entry, line);
+                }
+            }
+            Part::IfMissing(field, parts) => {
+                if field.value(result, entry).is_none() {
+                    render_parts(parts, result, entry, line);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the parts refer to a name entry outside of a `{#names}` section
+fn uses_entry(parts: &[Part]) -> bool {
+    parts.iter().any(|part| match part {
+        Part::Text(_) | Part::Names(_) => false,
+        Part::Value(placeholder) => placeholder.field.is_entry_field(),
+        Part::IfPresent(field, parts) | Part::IfMissing(field, parts) => {
+            field.is_entry_field() || uses_entry(parts)
+        }
+    })
+}
+
+impl Placeholder {
+    fn render(&self, result: &ScanResult, entry: Option<&NameEntry>) -> String {
+        let mut value = self
+            .field
+            .value(result, entry)
+            .unwrap_or_else(|| self.missing.clone());
+        if let Some(max_width) = self.max_width {
+            value = value.chars().take(max_width).collect();
+        }
+        let padding = self.width.saturating_sub(value.chars().count());
+        if self.align_right {
+            format!("{}{}", " ".repeat(padding), value)
+        } else {
+            format!("{}{}", value, " ".repeat(padding))
+        }
+    }
+}
+
+impl FromStr for Placeholder {
+    type Err = OutputError;
+
+    /// Parses `field[:[<|>][width][.max]][|missing]`
+    fn from_str(tag: &str) -> Result<Placeholder, OutputError> {
+        let (tag, missing) = match tag.find('|') {
+            Some(index) => (&tag[..index], &tag[index + 1..]),
+            None => (tag, ""),
+        };
+        let (field, spec) = match tag.find(':') {
+            Some(index) => (&tag[..index], &tag[index + 1..]),
+            None => (tag, ""),
+        };
+
+        let invalid_spec = || OutputError::TemplateError(format!("invalid width in '{{{}}}'", tag));
+        let align_right = spec.starts_with('>');
+        let spec = spec.trim_start_matches(['<', '>']);
+        let (width, max_width) = match spec.find('.') {
+            Some(index) => (&spec[..index], Some(&spec[index + 1..])),
+            None => (spec, None),
+        };
+        let width = if width.is_empty() {
+            0
+        } else {
+            width.parse().map_err(|_| invalid_spec())?
+        };
+        let max_width = match max_width {
+            Some(max_width) => Some(max_width.parse().map_err(|_| invalid_spec())?),
+            None => None,
+        };
+
+        Ok(Placeholder {
+            field: field.trim().parse()?,
+            align_right,
+            width,
+            max_width,
+            missing: String::from(missing),
+        })
+    }
+}
+
+impl FromStr for Template {
+    type Err = OutputError;
+
+    fn from_str(template: &str) -> Result<Template, OutputError> {
+        let mut chars = template.chars().peekable();
+        // The parts of each open section, innermost last, with the tag that opened it
+        let mut sections: Vec<(String, Vec<Part>)> = vec![(String::new(), Vec::new())];
+        let mut text = String::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    Some(other) => {
+                        text.push('\\');
+                        text.push(other);
+                    }
+                    None => text.push('\\'),
+                },
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(template_error("unmatched '}'")),
+                '{' => {
+                    let mut tag = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => tag.push(c),
+                            None => return Err(template_error("unclosed '{'")),
+                        }
+                    }
+
+                    let parts = &mut sections.last_mut().unwrap().1;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(text.split_off(0)));
+                    }
+
+                    if tag.starts_with(['#', '?', '^']) {
+                        sections.push((tag, Vec::new()));
+                    } else if let Some(name) = tag.strip_prefix('/') {
+                        if sections.len() == 1 {
+                            return Err(template_error(&format!("'{{{}}}' closes nothing", tag)));
+                        }
+                        let (opening, parts) = sections.pop().unwrap();
+                        if &opening[1..] != name {
+                            return Err(template_error(&format!(
+                                "'{{{}}}' is closed by '{{{}}}'",
+                                opening, tag
+                            )));
+                        }
+                        let section = match opening.chars().next() {
+                            Some('#') if name == "names" => Part::Names(parts),
+                            Some('#') => {
+                                return Err(template_error(&format!(
+                                    "only names can be repeated, not '{}'",
+                                    name
+                                )))
+                            }
+                            Some('?') => Part::IfPresent(name.parse()?, parts),
+                            _ => Part::IfMissing(name.parse()?, parts),
+                        };
+                        sections.last_mut().unwrap().1.push(section);
+                    } else {
+                        parts.push(Part::Value(tag.parse()?));
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+
+        if sections.len() > 1 {
+            let (opening, _) = sections.pop().unwrap();
+            return Err(template_error(&format!("'{{{}}}' is never closed", opening)));
+        }
+        let mut parts = sections.pop().unwrap().1;
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+}
+
+fn template_error(message: &str) -> OutputError {
+    OutputError::TemplateError(String::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use assert_matches::assert_matches;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn result() -> ScanResult {
+        let packet = packet(
+            Ipv4Addr::new(10, 1, 2, 3),
+            &[
+                (b"FILESERVER     ", 0x20, 0x0400),
+                (b"SPICE          ", 0x00, 0xC400),
+            ],
+            [0x2C, 0x41, 0x38, 0xBA, 0xC3, 0x64],
+        );
+        ScanResult::new(packet, Duration::from_millis(2))
+    }
+
+    fn render(template: &str) -> String {
+        let template: Template = template.parse().unwrap();
+        let mut out = Vec::new();
+        template.write(&[result()], &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn fills_in_padded_fields_and_missing_values() {
+        assert_eq!(
+            render("{ip:<10}|{workgroup:>7}|{hostname|-}|{mac:.8}{?hostname} ({hostname}){/hostname}"),
+            "10.1.2.3  |  SPICE|-|2C:41:38\n"
+        );
+        assert_eq!(render("{{{ip}}}\\t{^hostname}no dns{/hostname}"), "{10.1.2.3}\tno dns\n");
+    }
+
+    #[test]
+    fn repeats_name_entries() {
+        assert_eq!(
+            render("{ip}:{#names} {entry}<{suffix}>{/names}"),
+            "10.1.2.3: FILESERVER<20> SPICE<00>\n"
+        );
+        assert_eq!(
+            render("{ip}\\t{entry}<{suffix}>"),
+            "10.1.2.3\tFILESERVER<20>\n10.1.2.3\tSPICE<00>\n"
+        );
+    }
+
+    #[test]
+    fn name_is_the_computer_name_and_entry_the_entry_name() {
+        assert_eq!(
+            render("{entry}<{suffix}> of {name}"),
+            "FILESERVER<20> of FILESERVER\nSPICE<00> of FILESERVER\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert_matches!("{ip".parse::<Template>(), Err(OutputError::TemplateError(_)));
+        assert_matches!("{#names}{entry}".parse::<Template>(), Err(OutputError::TemplateError(_)));
+        assert_matches!("{?mac}{/ip}".parse::<Template>(), Err(OutputError::TemplateError(_)));
+        assert_matches!("{ip:wide}".parse::<Template>(), Err(OutputError::TemplateError(_)));
+        assert_matches!("{owner}".parse::<Template>(), Err(OutputError::UnknownFieldError(_)));
+    }
+}
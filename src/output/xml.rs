@@ -0,0 +1,310 @@
+use super::ScanInfo;
+use crate::oui::MacKind;
+use crate::roles::Role;
+use crate::scan_result::{HostState, ScanResult, Unanswered};
+use crate::timestamp::civil_from_days;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const XML_OUTPUT_VERSION: &str = "1.05";
+
+/// Writes the results in the format of nmap's `-oX` output, with the name
+/// table where nmap's `nbstat` script puts it, so existing nmap importers
+/// can read them
+///
+/// Closed targets are written as hosts that are up with the port closed.
+/// Targets that couldn't be probed are left out, as nmap does.
+pub fn write(
+    results: &[ScanResult],
+    unanswered: &[Unanswered],
+    info: &ScanInfo,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let (start, start_str) = timestamp(info.started);
+    let (end, end_str) = timestamp(info.finished);
+    let elapsed = info
+        .finished
+        .duration_since(info.started)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<!DOCTYPE nmaprun>")?;
+    writeln!(
+        out,
+        "<nmaprun scanner=\"nbtscanner\" args=\"{}\" start=\"{}\" startstr=\"{}\" version=\"{}\" xmloutputversion=\"{}\">",
+        escape(&info.command_line),
+        start,
+        start_str,
+        env!("CARGO_PKG_VERSION"),
+        XML_OUTPUT_VERSION
+    )?;
+    writeln!(
+        out,
+        "<scaninfo type=\"udp\" protocol=\"udp\" numservices=\"1\" services=\"137\"/>"
+    )?;
+
+    for result in results {
+        write_host(result, out)?;
+    }
+    let closed: Vec<&Unanswered> = unanswered
+        .iter()
+        .filter(|unanswered| unanswered.state == HostState::Closed)
+        .collect();
+    for unanswered in closed.iter() {
+        write_closed_host(unanswered, out)?;
+    }
+
+    let up = (results.len() + closed.len()) as u64;
+    writeln!(out, "<runstats>")?;
+    writeln!(
+        out,
+        "<finished time=\"{}\" timestr=\"{}\" elapsed=\"{:.2}\" summary=\"nbtscanner done at {}; {} IP addresses ({} hosts up) scanned in {:.2} seconds\" exit=\"success\"/>",
+        end, end_str, elapsed, end_str, info.targets, up, elapsed
+    )?;
+    writeln!(
+        out,
+        "<hosts up=\"{}\" down=\"{}\" total=\"{}\"/>",
+        up,
+        info.targets.saturating_sub(up),
+        info.targets
+    )?;
+    writeln!(out, "</runstats>")?;
+    writeln!(out, "</nmaprun>")
+}
+
+/// A host that answered with ICMP port unreachable
+fn write_closed_host(unanswered: &Unanswered, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "<host>")?;
+    writeln!(
+        out,
+        "<status state=\"up\" reason=\"port-unreach\" reason_ttl=\"0\"/>"
+    )?;
+    writeln!(out, "<address addr=\"{}\" addrtype=\"ipv4\"/>", unanswered.ip)?;
+    match unanswered.hostname {
+        Some(ref hostname) => {
+            writeln!(out, "<hostnames>")?;
+            writeln!(out, "<hostname name=\"{}\" type=\"user\"/>", escape(hostname))?;
+            writeln!(out, "</hostnames>")?;
+        }
+        None => writeln!(out, "<hostnames/>")?,
+    }
+    writeln!(out, "<ports>")?;
+    writeln!(out, "<port protocol=\"udp\" portid=\"137\">")?;
+    writeln!(
+        out,
+        "<state state=\"closed\" reason=\"port-unreach\" reason_ttl=\"0\"/>"
+    )?;
+    writeln!(out, "<service name=\"netbios-ns\" method=\"table\" conf=\"3\"/>")?;
+    writeln!(out, "</port>")?;
+    writeln!(out, "</ports>")?;
+    writeln!(out, "</host>")
+}
+
+fn write_host(result: &ScanResult, out: &mut dyn Write) -> io::Result<()> {
+    let packet = &result.packet;
+    let name = packet.name();
+    let workgroup = packet.workgroup().unwrap_or_default();
+    let mac = packet.mac_address();
+    let names = packet.names();
+    let roles: Vec<&str> = result.roles().into_iter().map(Role::name).collect();
+    // nbstat shows the first messenger name as the user
+    let user = result
+        .users
+        .as_ref()
+        .and_then(|users| users.first().cloned())
+        .unwrap_or_else(|| String::from("<unknown>"));
+
+    writeln!(out, "<host>")?;
+    writeln!(
+        out,
+        "<status state=\"up\" reason=\"udp-response\" reason_ttl=\"0\"/>"
+    )?;
+    writeln!(out, "<address addr=\"{}\" addrtype=\"ipv4\"/>", result.ip)?;
+    match result.vendor() {
+        // nmap only names real vendors, not the notes for zero or local MACs
+        Some(ref vendor) if result.mac_kind() == MacKind::Universal => writeln!(
+            out,
+            "<address addr=\"{}\" addrtype=\"mac\" vendor=\"{}\"/>",
+            mac,
+            escape(vendor)
+        )?,
+        _ => writeln!(out, "<address addr=\"{}\" addrtype=\"mac\"/>", mac)?,
+    }
+    match result.hostname {
+        Some(ref hostname) => {
+            writeln!(out, "<hostnames>")?;
+            writeln!(out, "<hostname name=\"{}\" type=\"user\"/>", escape(hostname))?;
+            writeln!(out, "</hostnames>")?;
+        }
+        None => writeln!(out, "<hostnames/>")?,
+    }
+    writeln!(out, "<ports>")?;
+    writeln!(out, "<port protocol=\"udp\" portid=\"137\">")?;
+    writeln!(
+        out,
+        "<state state=\"open\" reason=\"udp-response\" reason_ttl=\"0\"/>"
+    )?;
+    writeln!(
+        out,
+        "<service name=\"netbios-ns\" method=\"probed\" conf=\"10\"/>"
+    )?;
+    writeln!(out, "</port>")?;
+    writeln!(out, "</ports>")?;
+    if let Some(fingerprint) = result.fingerprint() {
+        writeln!(out, "<os>")?;
+        writeln!(
+            out,
+            "<osmatch name=\"{}\" accuracy=\"{}\" line=\"0\">",
+            escape(&fingerprint.os),
+            fingerprint.confidence
+        )?;
+        writeln!(
+            out,
+            "<osclass type=\"{}\" osfamily=\"{}\" accuracy=\"{}\"/>",
+            escape(&fingerprint.class),
+            escape(&fingerprint.os),
+            fingerprint.confidence
+        )?;
+        writeln!(out, "</osmatch>")?;
+        writeln!(out, "</os>")?;
+    }
+
+    // The same human readable summary nbstat prints, followed by its
+    // structured output
+    let mut summary = format!(
+        "NetBIOS name: {}, NetBIOS user: {}, NetBIOS MAC: {}\nNames:",
+        name,
+        user,
+        mac.to_lowercase()
+    );
+    for entry in names.iter() {
+        summary.push_str(&format!(
+            "\n  {:<16}<{:02x}>  Flags: <{}><{}>",
+            entry.name,
+            entry.suffix,
+            if entry.is_group() { "group" } else { "unique" },
+            if entry.is_active() { "active" } else { "inactive" }
+        ));
+    }
+    if !roles.is_empty() {
+        summary.push_str(&format!("\nRoles: {}", roles.join(", ")));
+    }
+
+    writeln!(out, "<hostscript>")?;
+    writeln!(out, "<script id=\"nbstat\" output=\"{}\">", escape(&summary))?;
+    writeln!(out, "<elem key=\"server_name\">{}</elem>", escape(&name))?;
+    writeln!(out, "<elem key=\"workgroup\">{}</elem>", escape(&workgroup))?;
+    writeln!(out, "<elem key=\"user\">{}</elem>", escape(&user))?;
+    writeln!(out, "<elem key=\"mac\">{}</elem>", mac.to_lowercase())?;
+    writeln!(out, "<table key=\"names\">")?;
+    for entry in names.iter() {
+        writeln!(out, "<table>")?;
+        writeln!(out, "<elem key=\"name\">{}</elem>", escape(&entry.name))?;
+        writeln!(out, "<elem key=\"suffix\">{}</elem>", entry.suffix)?;
+        writeln!(out, "<elem key=\"flags\">{}</elem>", entry.flags)?;
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "<table key=\"roles\">")?;
+    for role in roles.iter() {
+        writeln!(out, "<elem>{}</elem>", role)?;
+    }
+    writeln!(out, "</table>")?;
+    writeln!(out, "</script>")?;
+    writeln!(out, "</hostscript>")?;
+    writeln!(
+        out,
+        "<times srtt=\"{}\" rttvar=\"0\" to=\"{}\"/>",
+        result.rtt.as_micros(),
+        result.rtt.as_micros().max(100_000)
+    )?;
+    writeln!(out, "</host>")
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#xa;"),
+            // Characters XML 1.0 can't represent at all, e.g. the control
+            // bytes in __MSBROWSE__
+            c if (c as u32) < 0x20 && c != '\t' && c != '\r' => {
+                escaped.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Seconds since the epoch, and the same time in ctime format as nmap
+/// writes it (in UTC)
+fn timestamp(time: SystemTime) -> (u64, String) {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = seconds / 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    let time_of_day = seconds % 86_400;
+
+    let formatted = format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        DAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize],
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60,
+        year
+    );
+    (seconds, formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_timestamps_like_ctime() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_776_600_000);
+        assert_eq!(timestamp(time), (1_776_600_000, String::from("Sun Apr 19 12:00:00 2026")));
+    }
+
+    #[test]
+    fn lists_the_hosts_roles() {
+        let dc = packet(
+            Ipv4Addr::new(10, 1, 2, 3),
+            &[
+                (b"DC01           ", 0x20, 0x0400),
+                (b"CORP           ", 0x1C, 0x8400),
+            ],
+            [0; 6],
+        );
+        let mut out = Vec::new();
+        write_host(&ScanResult::new(dc, Duration::from_millis(1)), &mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("&#xa;Roles: dc, fs\">"));
+        assert!(xml.contains("<table key=\"roles\">\n<elem>dc</elem>\n<elem>fs</elem>\n</table>"));
+    }
+
+    #[test]
+    fn escapes_markup_and_control_characters() {
+        assert_eq!(escape("A&B <\"x\">"), "A&amp;B &lt;&quot;x&quot;&gt;");
+        assert_eq!(escape("\u{1}\u{2}__MSBROWSE__"), "\\x01\\x02__MSBROWSE__");
+    }
+}
@@ -0,0 +1,87 @@
+const ROUNDS: usize = 4;
+
+/// A keyed bijection over `0..size`.
+///
+/// Indexes are run through a small Feistel network over the smallest even
+/// bit width that covers `size`, re-encrypting any value that falls outside
+/// the range ("cycle walking"). Every index maps to a unique index and the
+/// mapping only needs the key schedule, not a table.
+#[derive(Debug, Clone)]
+pub struct Permutation {
+    size: u64,
+    half_bits: u32,
+    keys: [u64; ROUNDS],
+}
+
+impl Permutation {
+    pub fn new(size: u64, seed: u64) -> Permutation {
+        let bits = 64 - size.saturating_sub(1).max(1).leading_zeros();
+        let half_bits = bits.div_ceil(2);
+
+        let mut state = seed;
+        let mut keys = [0u64; ROUNDS];
+        for key in keys.iter_mut() {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            *key = mix(state);
+        }
+
+        Permutation {
+            size,
+            half_bits,
+            keys,
+        }
+    }
+
+    /// Maps `index` (which must be below `size`) to its permuted position
+    pub fn apply(&self, index: u64) -> u64 {
+        debug_assert!(index < self.size);
+        let mut value = self.encrypt(index);
+        while value >= self.size {
+            value = self.encrypt(value);
+        }
+        value
+    }
+
+    fn encrypt(&self, value: u64) -> u64 {
+        let mask = (1u64 << self.half_bits) - 1;
+        let mut left = value >> self.half_bits;
+        let mut right = value & mask;
+        for key in self.keys.iter() {
+            let next = left ^ (mix(right ^ key) & mask);
+            left = right;
+            right = next;
+        }
+        (left << self.half_bits) | right
+    }
+}
+
+// splitmix64 finalizer
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visits_every_index_exactly_once() {
+        for &size in [1u64, 2, 7, 254, 1000].iter() {
+            let permutation = Permutation::new(size, 42);
+            let mut seen: Vec<u64> = (0..size).map(|i| permutation.apply(i)).collect();
+            seen.sort_unstable();
+            assert_eq!(seen, (0..size).collect::<Vec<u64>>());
+        }
+    }
+
+    #[test]
+    fn order_depends_only_on_the_seed() {
+        let first: Vec<u64> = (0..254).map(|i| Permutation::new(254, 7).apply(i)).collect();
+        let again: Vec<u64> = (0..254).map(|i| Permutation::new(254, 7).apply(i)).collect();
+        let other: Vec<u64> = (0..254).map(|i| Permutation::new(254, 8).apply(i)).collect();
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+    }
+}
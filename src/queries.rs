@@ -0,0 +1,170 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use nbtscanner::inventory::{Inventory, Seen, Sighting};
+use nbtscanner::timestamp::format_utc;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// The `inventory` subcommand, which answers questions about the hosts
+/// recorded with `--db`
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("inventory")
+        .about("Queries the scan history recorded with --db")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(Arg::with_name("db")
+            .long("db")
+            .value_name("FILE")
+            .help("The inventory database")
+            .takes_value(true)
+            .required(true)
+        ).subcommand(SubCommand::with_name("scans")
+            .about("Lists the recorded scans")
+        ).subcommand(SubCommand::with_name("ip")
+            .about("Shows every time an IP address answered")
+            .arg(Arg::with_name("IP").required(true))
+        ).subcommand(SubCommand::with_name("mac")
+            .about("Shows every address and name a MAC address has been seen with")
+            .arg(Arg::with_name("MAC").required(true))
+        ).subcommand(SubCommand::with_name("name")
+            .about("Shows when each suffix of a NetBIOS name was first and last seen")
+            .arg(Arg::with_name("NAME").required(true))
+        ).subcommand(SubCommand::with_name("stale")
+            .about("Lists hosts that haven't answered a scan in a while")
+            .arg(Arg::with_name("days")
+                .long("days")
+                .value_name("N")
+                .help("How many days a host must have been missing for")
+                .takes_value(true)
+                .default_value("30")
+            )
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let path = matches.value_of("db").unwrap();
+    let inventory = match Inventory::open(Path::new(path)) {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            println!("Couldn't open inventory {}: {}", path, e);
+            std::process::exit(-1)
+        }
+    };
+
+    let queried = match matches.subcommand() {
+        ("scans", _) => inventory.scans().map(|scans| {
+            println!("{:<6}{:<21}{:<8}{:<8}COMMAND", "ID", "STARTED", "TARGETS", "HOSTS");
+            for scan in scans {
+                println!(
+                    "{:<6}{:<21}{:<8}{:<8}{}",
+                    scan.id,
+                    format_utc(scan.started),
+                    scan.targets,
+                    scan.hosts,
+                    scan.command_line
+                );
+            }
+        }),
+        ("ip", Some(arguments)) => {
+            let ip: Ipv4Addr = match arguments.value_of("IP").unwrap().parse() {
+                Ok(ip) => ip,
+                Err(_) => {
+                    println!("Expected an IPv4 address");
+                    std::process::exit(-1)
+                }
+            };
+            inventory.ip_history(ip).map(|observations| {
+                println!("{:<21}{:<6}{:<16}{:<16}MAC", "SEEN", "SCAN", "NAME", "WORKGROUP");
+                for observation in observations {
+                    println!(
+                        "{:<21}{:<6}{:<16}{:<16}{}",
+                        format_utc(observation.seen),
+                        observation.scan_id,
+                        observation.name,
+                        observation.workgroup.unwrap_or_default(),
+                        observation.mac
+                    );
+                }
+            })
+        }
+        ("mac", Some(arguments)) => {
+            let mac = arguments.value_of("MAC").unwrap();
+            inventory.mac_seen(mac).and_then(|seen| {
+                match seen {
+                    Some(seen) => println!("{} {}\n", mac, describe_seen(&seen)),
+                    None => println!("{} has never been seen\n", mac),
+                }
+                inventory
+                    .mac_sightings(mac)
+                    .map(|sightings| print_sightings(&sightings))
+            })
+        }
+        ("name", Some(arguments)) => {
+            let name = arguments.value_of("NAME").unwrap();
+            inventory.name_seen(name).map(|seen| {
+                if seen.is_empty() {
+                    println!("{} has never been seen", name);
+                }
+                for (suffix, seen) in seen {
+                    println!("{}<{:02X}> {}", name.to_uppercase(), suffix, describe_seen(&seen));
+                }
+            })
+        }
+        ("stale", Some(arguments)) => {
+            let days: u64 = match arguments.value_of("days").unwrap().parse() {
+                Ok(days) => days,
+                Err(_) => {
+                    println!("The number of days must be a positive integer");
+                    std::process::exit(-1)
+                }
+            };
+            let cutoff = days
+                .checked_mul(SECONDS_PER_DAY)
+                .and_then(|seconds| SystemTime::now().checked_sub(Duration::from_secs(seconds)));
+            let cutoff = match cutoff {
+                Some(cutoff) => cutoff,
+                None => {
+                    println!("{} days reaches back further than the clock can", days);
+                    std::process::exit(-1)
+                }
+            };
+            inventory
+                .not_seen_since(cutoff)
+                .map(|sightings| print_sightings(&sightings))
+        }
+        _ => unreachable!("clap requires a subcommand"),
+    };
+
+    if let Err(e) = queried {
+        println!("{}", e);
+        std::process::exit(-1)
+    }
+}
+
+fn describe_seen(seen: &Seen) -> String {
+    format!(
+        "was first seen {}, last seen {} at {}",
+        format_utc(seen.first_seen),
+        format_utc(seen.last_seen),
+        seen.last_ip
+    )
+}
+
+fn print_sightings(sightings: &[Sighting]) {
+    println!(
+        "{:<16}{:<16}{:<18}{:<21}{:<21}SCANS",
+        "NAME", "LAST IP", "MAC", "FIRST SEEN", "LAST SEEN"
+    );
+    for sighting in sightings {
+        println!(
+            "{:<16}{:<16}{:<18}{:<21}{:<21}{}",
+            sighting.name,
+            sighting.ip,
+            sighting.mac,
+            format_utc(sighting.first_seen),
+            format_utc(sighting.last_seen),
+            sighting.times_seen
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use self::ResolveError::*;
+use crate::ip_range::{IpParserError, TargetSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs};
+use std::path::Path;
+
+/// Looks up the IPv4 addresses of a hostname
+pub trait Resolver {
+    fn resolve(&self, hostname: &str) -> io::Result<Vec<Ipv4Addr>>;
+}
+
+/// Resolves hostnames through the operating system's resolver
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, hostname: &str) -> io::Result<Vec<Ipv4Addr>> {
+        let addresses = (hostname, 0)
+            .to_socket_addrs()?
+            .filter_map(|address| match address.ip() {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .collect();
+        Ok(addresses)
+    }
+}
+
+#[derive(Debug)]
+pub enum ResolveError {
+    SpecError(IpParserError),
+    LookupError(String, io::Error),
+    NoAddressError(String),
+    HostsFileError(String, io::Error),
+}
+
+impl Error for ResolveError {}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            SpecError(e) => e.fmt(f),
+            LookupError(hostname, e) => write!(f, "Couldn't resolve {}: {}", hostname, e),
+            NoAddressError(hostname) => write!(f, "{} has no IPv4 address", hostname),
+            HostsFileError(path, e) => write!(f, "Couldn't read hosts file {}: {}", path, e),
+        }
+    }
+}
+
+/// Parses a target given on the command line: either an IP address or range,
+/// or a hostname to look up with `resolver`
+pub fn resolve_target(spec: &str, resolver: &dyn Resolver) -> Result<TargetSet, ResolveError> {
+    match spec.parse::<TargetSet>() {
+        Ok(targets) => Ok(targets),
+        Err(e) if !is_hostname(spec) => Err(SpecError(e)),
+        Err(_) => {
+            let addresses = resolver
+                .resolve(spec)
+                .map_err(|e| LookupError(String::from(spec), e))?;
+            if addresses.is_empty() {
+                return Err(NoAddressError(String::from(spec)));
+            }
+            let mut targets = TargetSet::new();
+            for ip in addresses {
+                targets.add_host(ip, spec);
+            }
+            Ok(targets)
+        }
+    }
+}
+
+/// Reads the targets listed in an /etc/hosts style or LMHOSTS file
+pub fn read_hosts_file(path: &Path) -> Result<TargetSet, ResolveError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| HostsFileError(path.display().to_string(), e))?;
+    let mut targets = TargetSet::new();
+    for (ip, hostname) in parse_hosts_file(&contents) {
+        targets.add_host(ip, &hostname);
+    }
+    Ok(targets)
+}
+
+/// Parses `<ip> <name> [aliases or #keywords...]` lines, keeping the first
+/// name of each IPv4 entry. Comments, LMHOSTS directives like `#INCLUDE`, and
+/// IPv6 entries are skipped.
+fn parse_hosts_file(contents: &str) -> Vec<(Ipv4Addr, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            let ip = tokens.next()?.parse::<Ipv4Addr>().ok()?;
+            let name = tokens.next().filter(|name| !name.starts_with('#'))?;
+            // LMHOSTS names may be quoted to carry a suffix, e.g. "SERVER  \0x20"
+            let name = name.trim_matches('"');
+            Some((ip, String::from(name)))
+        })
+        .collect()
+}
+
+fn is_hostname(spec: &str) -> bool {
+    !spec.is_empty()
+        && spec.chars().any(|c| c.is_ascii_alphabetic())
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+}
+
+/// Whether a target's hostname plausibly refers to the same machine as the
+/// NetBIOS name it answered with. NetBIOS names are at most 15 characters and
+/// case-insensitive, and don't include the DNS domain.
+pub fn names_agree(hostname: &str, netbios_name: &str) -> bool {
+    if hostname.parse::<Ipv4Addr>().is_ok() {
+        return true;
+    }
+    let short_name: String = hostname.split('.').next().unwrap_or("").chars().take(15).collect();
+    short_name.eq_ignore_ascii_case(netbios_name.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use std::collections::HashMap;
+
+    struct FakeResolver(HashMap<&'static str, Vec<Ipv4Addr>>);
+
+    impl Resolver for FakeResolver {
+        fn resolve(&self, hostname: &str) -> io::Result<Vec<Ipv4Addr>> {
+            self.0
+                .get(hostname)
+                .cloned()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "unknown host"))
+        }
+    }
+
+    fn fake_resolver() -> FakeResolver {
+        let mut hosts = HashMap::new();
+        hosts.insert("fileserver.corp.local", vec![Ipv4Addr::new(10, 1, 2, 3)]);
+        FakeResolver(hosts)
+    }
+
+    #[test]
+    fn resolves_hostnames_and_labels_the_targets() {
+        let targets = resolve_target("fileserver.corp.local", &fake_resolver()).unwrap();
+        assert_eq!(targets.first(), Some(Ipv4Addr::new(10, 1, 2, 3)));
+        assert_eq!(
+            targets.hostname(Ipv4Addr::new(10, 1, 2, 3)),
+            Some("fileserver.corp.local")
+        );
+    }
+
+    #[test]
+    fn ip_specs_are_not_looked_up() {
+        let targets = resolve_target("10.192.4.35-37", &fake_resolver()).unwrap();
+        assert_eq!(targets.len(), 3);
+        assert_matches!(
+            resolve_target("10.320.4.0/24", &fake_resolver()),
+            Err(ResolveError::SpecError(IpParserError::BaseIpError))
+        );
+        assert_matches!(
+            resolve_target("printer.corp.local", &fake_resolver()),
+            Err(ResolveError::LookupError(_, _))
+        );
+    }
+
+    #[test]
+    fn parses_hosts_and_lmhosts_lines() {
+        let contents = "# comment\n\
+                        127.0.0.1 localhost\n\
+                        ::1 localhost ip6-localhost\n\
+                        10.1.2.3   FILESERVER   #PRE #DOM:CORP\n\
+                        10.1.2.4 \"PRINTER        \\0x20\" #PRE\n\
+                        #INCLUDE \\\\server\\share\\lmhosts\n";
+        let entries = parse_hosts_file(contents);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], (Ipv4Addr::new(10, 1, 2, 3), String::from("FILESERVER")));
+        assert_eq!(entries[2].1, "PRINTER");
+    }
+
+    #[test]
+    fn compares_hostnames_with_netbios_names() {
+        assert!(names_agree("fileserver.corp.local", "FILESERVER"));
+        assert!(!names_agree("fileserver.corp.local", "BACKUP01"));
+    }
+}
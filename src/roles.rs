@@ -0,0 +1,163 @@
+use self::RoleError::*;
+use crate::nbt_packet::{NameEntry, NetBiosPacket};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+const FILE_SERVER_SUFFIX: u8 = 0x20;
+const DOMAIN_MASTER_BROWSER_SUFFIX: u8 = 0x1B;
+const DOMAIN_CONTROLLER_SUFFIX: u8 = 0x1C;
+const MASTER_BROWSER_SUFFIX: u8 = 0x1D;
+
+/// A service a host announces through the names it registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Role {
+    /// Registers the domain's <1C> group name
+    #[serde(rename = "dc")]
+    DomainController,
+    /// Registers the domain's <1B> name, usually the PDC
+    #[serde(rename = "dmb")]
+    DomainMasterBrowser,
+    /// Registers the unique <1D> name, keeping the browse list for its
+    /// subnet. __MSBROWSE__<01> alone isn't enough, as every browser in the
+    /// workgroup can hold it.
+    #[serde(rename = "lmb")]
+    MasterBrowser,
+    /// Registers <20>, the server service
+    #[serde(rename = "fs")]
+    FileServer,
+}
+
+const ROLES: [Role; 4] = [
+    Role::DomainController,
+    Role::DomainMasterBrowser,
+    Role::MasterBrowser,
+    Role::FileServer,
+];
+
+#[derive(Debug)]
+pub enum RoleError {
+    UnknownRoleError(String),
+}
+
+impl Error for RoleError {}
+
+impl fmt::Display for RoleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            UnknownRoleError(role) => write!(
+                f,
+                "Unknown role '{}', expected dc, dmb, lmb, mb (either master browser) or fs",
+                role
+            ),
+        }
+    }
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::DomainController => "dc",
+            Role::DomainMasterBrowser => "dmb",
+            Role::MasterBrowser => "lmb",
+            Role::FileServer => "fs",
+        }
+    }
+
+    /// The roles the host's names announce
+    pub fn of(packet: &NetBiosPacket) -> Vec<Role> {
+        Role::from_names(&packet.names())
+    }
+
+    /// The roles announced by a name table
+    pub fn from_names(names: &[NameEntry]) -> Vec<Role> {
+        let has = |suffix: u8, group: bool| {
+            names
+                .iter()
+                .any(|entry| entry.suffix == suffix && entry.is_group() == group)
+        };
+        ROLES
+            .iter()
+            .cloned()
+            .filter(|role| match role {
+                Role::DomainController => has(DOMAIN_CONTROLLER_SUFFIX, true),
+                Role::DomainMasterBrowser => has(DOMAIN_MASTER_BROWSER_SUFFIX, false),
+                Role::MasterBrowser => has(MASTER_BROWSER_SUFFIX, false),
+                Role::FileServer => has(FILE_SERVER_SUFFIX, false),
+            })
+            .collect()
+    }
+
+    /// Parses a comma separated list of roles, e.g. `dc,mb`, where `mb`
+    /// stands for both kinds of master browser
+    pub fn parse_list(roles: &str) -> Result<Vec<Role>, RoleError> {
+        let mut parsed = Vec::new();
+        for role in roles.split(',').map(str::trim) {
+            if role == "mb" {
+                parsed.extend_from_slice(&[Role::DomainMasterBrowser, Role::MasterBrowser]);
+            } else {
+                parsed.push(role.parse()?);
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Role {
+    type Err = RoleError;
+
+    fn from_str(role: &str) -> Result<Role, RoleError> {
+        ROLES
+            .iter()
+            .find(|candidate| candidate.name() == role)
+            .cloned()
+            .ok_or_else(|| UnknownRoleError(String::from(role)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn finds_roles_from_registered_names() {
+        let mut names: Vec<(&[u8; 15], u8, u16)> = vec![
+            (b"DC01           ", 0x20, 0x0400),
+            (b"CORP           ", 0x1C, 0x8400),
+            (b"CORP           ", 0x1B, 0x0400),
+            (b"\x01\x02__MSBROWSE__\x02", 0x01, 0x8400),
+            (b"CORP           ", 0x00, 0x8400),
+        ];
+        let packet = test_support::packet(Ipv4Addr::new(10, 1, 2, 3), &names, [0; 6]);
+        assert_eq!(
+            Role::of(&packet),
+            vec![
+                Role::DomainController,
+                Role::DomainMasterBrowser,
+                Role::FileServer
+            ]
+        );
+
+        names.push((b"CORP           ", 0x1D, 0x0400));
+        let packet = test_support::packet(Ipv4Addr::new(10, 1, 2, 3), &names, [0; 6]);
+        assert_eq!(Role::of(&packet), ROLES.to_vec());
+        assert_eq!(
+            Role::parse_list("dc, mb").unwrap(),
+            vec![
+                Role::DomainController,
+                Role::DomainMasterBrowser,
+                Role::MasterBrowser
+            ]
+        );
+        assert!(Role::parse_list("dc,pdc").is_err());
+    }
+}
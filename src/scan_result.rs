@@ -0,0 +1,94 @@
+use crate::fingerprint::{self, Fingerprint};
+use crate::nbt_packet::NetBiosPacket;
+use crate::oui::{self, MacKind};
+use crate::roles::Role;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+/// How a target answered the probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostState {
+    /// Replied with its name table
+    Responding,
+    /// Up, but answered with ICMP port unreachable, so NetBIOS is off
+    Closed,
+    /// Nothing came back before the timeout: the host is down or a firewall
+    /// dropped the probe
+    NoReply,
+    /// The probe couldn't be sent from this machine
+    Error,
+}
+
+impl HostState {
+    pub fn name(self) -> &'static str {
+        match self {
+            HostState::Responding => "responding",
+            HostState::Closed => "closed",
+            HostState::NoReply => "no reply",
+            HostState::Error => "error",
+        }
+    }
+}
+
+/// A target that didn't answer with a name table, and why
+#[derive(Debug, Clone)]
+pub struct Unanswered {
+    pub ip: Ipv4Addr,
+    /// The hostname the target was given as, if it wasn't given by address
+    pub hostname: Option<String>,
+    pub state: HostState,
+    /// What went wrong, for local errors
+    pub error: Option<String>,
+}
+
+/// What the scan learned about one target that answered
+#[derive(Clone)]
+pub struct ScanResult {
+    pub ip: Ipv4Addr,
+    /// The hostname the target was given as, if it wasn't given by address
+    pub hostname: Option<String>,
+    /// Time from sending the probe that was answered to receiving the reply
+    pub rtt: Duration,
+    /// How many probes were sent before one was answered
+    pub attempts: u32,
+    pub packet: NetBiosPacket,
+    /// The users logged in, from the host's messenger names. Only looked up
+    /// when asked for, since it says who is sitting where.
+    pub users: Option<Vec<String>>,
+}
+
+impl ScanResult {
+    pub fn new(packet: NetBiosPacket, rtt: Duration) -> ScanResult {
+        ScanResult {
+            ip: packet.ip,
+            hostname: None,
+            rtt,
+            attempts: 1,
+            packet,
+            users: None,
+        }
+    }
+
+    /// The vendor of the host's network card, or a note on why the MAC
+    /// address can't tell (all-zero or locally administered)
+    pub fn vendor(&self) -> Option<String> {
+        oui::describe(self.packet.mac_bytes())
+    }
+
+    pub fn mac_kind(&self) -> MacKind {
+        MacKind::of(self.packet.mac_bytes())
+    }
+
+    /// What kind of device the host probably is, from the fingerprint rules
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        fingerprint::fingerprint(self)
+    }
+
+    /// The services the host announces, such as domain controller or
+    /// master browser
+    pub fn roles(&self) -> Vec<Role> {
+        Role::of(&self.packet)
+    }
+}
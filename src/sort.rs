@@ -0,0 +1,185 @@
+use self::SortError::*;
+use crate::scan_result::ScanResult;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// A value results can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Ip,
+    Name,
+    Workgroup,
+    Mac,
+    Rtt,
+    Vendor,
+}
+
+const SORT_KEYS: [SortKey; 6] = [
+    SortKey::Ip,
+    SortKey::Name,
+    SortKey::Workgroup,
+    SortKey::Mac,
+    SortKey::Rtt,
+    SortKey::Vendor,
+];
+
+#[derive(Debug)]
+pub enum SortError {
+    UnknownSortKeyError(String),
+}
+
+impl Error for SortError {}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            UnknownSortKeyError(key) => write!(
+                f,
+                "Unknown sort key '{}', expected ip, name, workgroup, mac, rtt or vendor",
+                key
+            ),
+        }
+    }
+}
+
+impl SortKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Ip => "ip",
+            SortKey::Name => "name",
+            SortKey::Workgroup => "workgroup",
+            SortKey::Mac => "mac",
+            SortKey::Rtt => "rtt",
+            SortKey::Vendor => "vendor",
+        }
+    }
+
+    /// Hosts without a workgroup or vendor sort after the ones with one
+    fn compare(self, a: &ScanResult, b: &ScanResult) -> Ordering {
+        match self {
+            SortKey::Ip => a.ip.cmp(&b.ip),
+            SortKey::Name => a.packet.name().cmp(&b.packet.name()),
+            SortKey::Workgroup => missing_last(a.packet.workgroup(), b.packet.workgroup()),
+            SortKey::Mac => a.packet.mac_bytes().cmp(&b.packet.mac_bytes()),
+            SortKey::Rtt => a.rtt.cmp(&b.rtt),
+            SortKey::Vendor => missing_last(a.vendor(), b.vendor()),
+        }
+    }
+}
+
+fn missing_last(a: Option<String>, b: Option<String>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = SortError;
+
+    fn from_str(key: &str) -> Result<SortKey, SortError> {
+        SORT_KEYS
+            .iter()
+            .find(|candidate| candidate.name() == key)
+            .cloned()
+            .ok_or_else(|| UnknownSortKeyError(String::from(key)))
+    }
+}
+
+/// The keys to sort by, most significant first, each either ascending or
+/// descending. Hosts equal on every key keep their order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortOrder {
+    pub keys: Vec<(SortKey, bool)>,
+}
+
+impl Default for SortOrder {
+    /// By address, the order scans have always printed in
+    fn default() -> SortOrder {
+        SortOrder {
+            keys: vec![(SortKey::Ip, false)],
+        }
+    }
+}
+
+impl SortOrder {
+    pub fn sort(&self, results: &mut [ScanResult]) {
+        results.sort_by(|a, b| self.compare(a, b));
+    }
+
+    pub fn compare(&self, a: &ScanResult, b: &ScanResult) -> Ordering {
+        self.keys
+            .iter()
+            .map(|(key, descending)| {
+                let ordering = key.compare(a, b);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = SortError;
+
+    /// Parses a comma separated list of keys, e.g. `workgroup,name`, where
+    /// a leading `-` sorts by that key in descending order, e.g. `-rtt`
+    fn from_str(order: &str) -> Result<SortOrder, SortError> {
+        let keys = order
+            .split(',')
+            .map(str::trim)
+            .map(|key| match key.strip_prefix('-') {
+                Some(key) => key.parse().map(|key| (key, true)),
+                None => key.parse().map(|key| (key, false)),
+            })
+            .collect::<Result<Vec<(SortKey, bool)>, SortError>>()?;
+        Ok(SortOrder { keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn result(
+        last_octet: u8,
+        name: &[u8; 15],
+        workgroup: Option<&[u8; 15]>,
+        rtt_ms: u64,
+    ) -> ScanResult {
+        let mut names = vec![(name, 0x00, 0x0400)];
+        if let Some(workgroup) = workgroup {
+            names.push((workgroup, 0x00, 0x8400));
+        }
+        let packet = packet(Ipv4Addr::new(10, 0, 0, last_octet), &names, [0; 6]);
+        ScanResult::new(packet, Duration::from_millis(rtt_ms))
+    }
+
+    #[test]
+    fn sorts_by_several_keys() {
+        let mut results = vec![
+            result(1, b"PRINTER        ", None, 5),
+            result(2, b"WS-2           ", Some(b"CORP           "), 9),
+            result(3, b"LAB1           ", Some(b"LAB            "), 1),
+            result(4, b"WS-1           ", Some(b"CORP           "), 9),
+            result(5, b"DC01           ", Some(b"CORP           "), 2),
+        ];
+        let order: SortOrder = "workgroup, -rtt, name".parse().unwrap();
+        order.sort(&mut results);
+        let ips: Vec<u8> = results.iter().map(|result| result.ip.octets()[3]).collect();
+        assert_eq!(ips, vec![4, 2, 5, 3, 1]);
+
+        assert!("workgroup,owner".parse::<SortOrder>().is_err());
+    }
+}
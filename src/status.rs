@@ -0,0 +1,107 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How often the line is redrawn, so fast scans don't spend their time
+/// writing to the terminal
+const REDRAW_INTERVAL_MILLIS: u64 = 100;
+
+/// A single line on stderr showing how far a scan has got, redrawn in place.
+/// It is only drawn when stderr is a terminal, so logs and pipes stay clean.
+pub struct StatusLine {
+    enabled: bool,
+    total: u64,
+    started: Instant,
+    last_drawn: Option<Instant>,
+    shown: bool,
+}
+
+impl StatusLine {
+    /// A line for a scan of `total` targets
+    pub fn new(total: u64, enabled: bool) -> StatusLine {
+        StatusLine {
+            enabled: enabled && stderr_is_terminal(),
+            total,
+            started: Instant::now(),
+            last_drawn: None,
+            shown: false,
+        }
+    }
+
+    /// Redraws the line, unless it was drawn very recently
+    pub fn update(&mut self, sent: u64, replies: u64, in_flight: u64) {
+        if !self.enabled {
+            return;
+        }
+        let interval = Duration::from_millis(REDRAW_INTERVAL_MILLIS);
+        if self.last_drawn.is_some_and(|drawn| drawn.elapsed() < interval) {
+            return;
+        }
+        let line = render(self.total, sent, replies, in_flight, self.started.elapsed());
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        write!(stderr, "\r{}\x1b[K", line).ok();
+        stderr.flush().ok();
+        self.last_drawn = Some(Instant::now());
+        self.shown = true;
+    }
+
+    /// Removes the line, so something else can be written. The next update
+    /// draws it again.
+    pub fn clear(&mut self) {
+        if self.shown {
+            eprint!("\r\x1b[K");
+            self.shown = false;
+            self.last_drawn = None;
+        }
+    }
+}
+
+fn stderr_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDERR_FILENO) == 1 }
+}
+
+/// e.g. `Sent 1200/65536, 37 replies, 64 in flight, 850/s, 0:00:01 elapsed, ETA 0:01:15`
+fn render(total: u64, sent: u64, replies: u64, in_flight: u64, elapsed: Duration) -> String {
+    let done = sent.saturating_sub(in_flight);
+    let rate = done as f64 / elapsed.as_secs_f64().max(0.001);
+    let eta = if done == 0 {
+        String::from("-")
+    } else {
+        clock(Duration::from_secs_f64(total.saturating_sub(done) as f64 / rate))
+    };
+    format!(
+        "Sent {}/{}, {} repl{}, {} in flight, {:.0}/s, {} elapsed, ETA {}",
+        sent,
+        total,
+        replies,
+        if replies == 1 { "y" } else { "ies" },
+        in_flight,
+        rate,
+        clock(elapsed),
+        eta
+    )
+}
+
+/// Formats a duration as h:mm:ss
+pub fn clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_rate_and_time_left() {
+        assert_eq!(
+            render(1000, 264, 12, 64, Duration::from_secs(4)),
+            "Sent 264/1000, 12 replies, 64 in flight, 50/s, 0:00:04 elapsed, ETA 0:00:16"
+        );
+        assert_eq!(
+            render(70000, 64, 1, 64, Duration::from_millis(300)),
+            "Sent 64/70000, 1 reply, 64 in flight, 0/s, 0:00:00 elapsed, ETA -"
+        );
+        assert_eq!(clock(Duration::from_secs(2 * 3600 + 5 * 60 + 9)), "2:05:09");
+    }
+}
@@ -2,14 +2,14 @@ use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::vec::Vec;
-use crate::nbt_packet::NetBiosPacket;
 
-pub struct ThreadPool {
+pub struct ThreadPool<T> {
     workers: Vec<Worker>,
-    sender: mpsc::Sender<Message>,
+    sender: mpsc::SyncSender<Message<T>>,
+    results: mpsc::Receiver<T>,
 }
 
-impl ThreadPool {
+impl<T: Send + 'static> ThreadPool<T> {
     /// Creates a new thread pool
     ///
     /// The size is the number of threads in the pool.
@@ -17,25 +17,31 @@ impl ThreadPool {
     /// # Panics
     ///
     /// The `new` function will panic if size is zero or below.
-    pub fn new(size: usize) -> ThreadPool {
+    pub fn new(size: usize) -> ThreadPool<T> {
         assert!(size > 0);
 
-        let (sender, receiver) = mpsc::channel();
+        // Bound the queue so large target sets aren't expanded into jobs all at once
+        let (sender, receiver) = mpsc::sync_channel(size * 2);
+        let (result_sender, results) = mpsc::channel();
 
         let receiver = Arc::new(Mutex::new(receiver));
 
         let mut workers = Vec::with_capacity(size);
 
         for _ in 0..size {
-            workers.push(Worker::new(Arc::clone(&receiver)));
+            workers.push(Worker::new(Arc::clone(&receiver), result_sender.clone()));
         }
 
-        ThreadPool { workers, sender }
+        ThreadPool {
+            workers,
+            sender,
+            results,
+        }
     }
 
     pub fn execute<F>(&self, f: F)
     where
-        F: FnOnce() -> Option<NetBiosPacket> + Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
     {
         let job = Box::new(f);
 
@@ -43,65 +49,73 @@ impl ThreadPool {
         self.sender.send(Message::Process(job)).unwrap();
     }
 
+    /// Returns the result of a finished job, if there is one
+    pub fn try_result(&self) -> Option<T> {
+        self.results.try_recv().ok()
+    }
+
+    /// Waits for the next job to finish. Returns `None` once the pool has
+    /// been stopped and every worker has exited.
+    pub fn next_result(&self) -> Option<T> {
+        self.results.recv().ok()
+    }
+
     pub fn stop(&self) {
         for _ in &self.workers {
             self.sender.send(Message::Terminate).expect("Terminating workers failed");
         }
     }
 
-    pub fn join_all(self) -> Vec<NetBiosPacket> {
-        let mut results: Vec<NetBiosPacket> = Vec::with_capacity(255);
+    pub fn join_all(self) {
         for worker in self.workers {
-            results.append(&mut worker.join());
+            worker.join();
         }
-        results
     }
 }
 
-trait FnBox {
-    fn call_box(self: Box<Self>) -> Option<NetBiosPacket>;
+trait FnBox<T> {
+    fn call_box(self: Box<Self>) -> T;
 }
 
-impl<F: FnOnce() -> Option<NetBiosPacket>> FnBox for F {
-    fn call_box(self: Box<F>) -> Option<NetBiosPacket> {
+impl<T, F: FnOnce() -> T> FnBox<T> for F {
+    fn call_box(self: Box<F>) -> T {
         (*self)()
     }
 }
 
-type Job = Box<dyn FnBox + Send + 'static>;
+type Job<T> = Box<dyn FnBox<T> + Send + 'static>;
 
 struct Worker {
-    thread: thread::JoinHandle<Vec<NetBiosPacket>>,
+    thread: thread::JoinHandle<()>,
 }
 
-enum Message {
-    Process(Job),
+enum Message<T> {
+    Process(Job<T>),
     Terminate,
 }
 
 impl Worker {
-    fn new(receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
-        let thread = thread::spawn(move || {
-            let mut thread_results: Vec<NetBiosPacket> = Vec::with_capacity(4);
-            loop {
-                let message = match receiver.lock().unwrap().recv() {
-                    Ok(message) => message,
-                    Err(_) => {
+    fn new<T: Send + 'static>(
+        receiver: Arc<Mutex<mpsc::Receiver<Message<T>>>>,
+        results: mpsc::Sender<T>,
+    ) -> Worker {
+        let thread = thread::spawn(move || loop {
+            let message = match receiver.lock().unwrap().recv() {
+                Ok(message) => message,
+                Err(_) => {
+                    break;
+                }
+            };
+
+            // Execute the closure from execute
+            match message {
+                Message::Process(job) => {
+                    if results.send(job.call_box()).is_err() {
                         break;
                     }
-                };
-
-                // Execute the closure from execute
-                match message {
-                    Message::Process(job) => {
-                        if let Some(packet) = job.call_box() {
-                            thread_results.push(packet);
-                        }
-                    }
-                    Message::Terminate => break,
                 }
+                Message::Terminate => break,
             }
-            thread_results
         });
 
         Worker { thread }
@@ -109,7 +123,7 @@ impl Worker {
 
     // Interface to allow calling thread to await execution of
     // workers
-    fn join(self) -> Vec<NetBiosPacket> {
+    fn join(self) {
         self.thread.join().unwrap()
     }
 }
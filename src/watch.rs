@@ -0,0 +1,201 @@
+use crate::diff::{self, Change, Host};
+use crate::ip_range::TargetSet;
+use crate::scan_result::ScanResult;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::Ipv4Addr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How quickly stable hosts are probed less often. Time is counted in
+/// rounds, so the schedule doesn't depend on how long each round takes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// How many unchanged rounds it takes before a host is probed half as
+    /// often
+    pub rounds_per_step: u32,
+    /// Stable hosts are probed at least every 2^max_steps rounds
+    pub max_steps: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            rounds_per_step: 6,
+            max_steps: 3,
+        }
+    }
+}
+
+impl Backoff {
+    /// How many rounds to wait before probing a host that has answered
+    /// unchanged `stable_rounds` times in a row
+    fn interval(&self, stable_rounds: u32) -> u64 {
+        let steps = (stable_rounds / self.rounds_per_step.max(1)).min(self.max_steps);
+        1 << steps
+    }
+}
+
+/// A change seen while watching, and when it was seen
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// Seconds since the epoch
+    pub time: u64,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl Event {
+    pub fn new(change: Change) -> Event {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Event { time, change }
+    }
+}
+
+struct Watched {
+    host: Host,
+    /// How many rounds in a row it answered unchanged
+    stable_rounds: u32,
+    /// The round it's next probed in
+    next_round: u64,
+}
+
+/// Remembers what every host looked like the last time it was probed, so
+/// each round only has to report what changed.
+///
+/// Hosts that keep answering unchanged are probed less and less often, as
+/// set by its `Backoff`. Addresses that haven't answered are probed every
+/// round so new hosts are noticed straight away.
+#[derive(Default)]
+pub struct Watcher {
+    hosts: BTreeMap<Ipv4Addr, Watched>,
+    round: u64,
+    backoff: Backoff,
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher::default()
+    }
+
+    pub fn with_backoff(backoff: Backoff) -> Watcher {
+        Watcher {
+            backoff,
+            ..Watcher::default()
+        }
+    }
+
+    /// How many rounds have been completed
+    pub fn rounds(&self) -> u64 {
+        self.round
+    }
+
+    /// The targets to probe this round
+    pub fn due(&self, targets: &TargetSet) -> TargetSet {
+        let resting: BTreeSet<Ipv4Addr> = self
+            .hosts
+            .iter()
+            .filter(|(_, watched)| watched.next_round > self.round)
+            .map(|(ip, _)| *ip)
+            .collect();
+        targets.without(&resting)
+    }
+
+    /// Takes in the results of probing `probed` and returns what changed.
+    /// The first round only records the hosts, so it never reports changes.
+    pub fn update(&mut self, probed: &TargetSet, results: &[ScanResult]) -> Vec<Change> {
+        let answered: BTreeMap<Ipv4Addr, Host> =
+            results.iter().map(|result| (result.ip, Host::from(result))).collect();
+
+        // Hosts that weren't probed this round are assumed unchanged
+        let before: Vec<Host> = self.hosts.values().map(|watched| watched.host.clone()).collect();
+        let after: Vec<Host> = self
+            .hosts
+            .values()
+            .filter(|watched| !probed.contains(watched.host.ip))
+            .map(|watched| watched.host.clone())
+            .chain(answered.values().cloned())
+            .collect();
+        let changes = if self.round == 0 {
+            Vec::new()
+        } else {
+            diff::diff(&before, &after)
+        };
+
+        let round = self.round;
+        let backoff = self.backoff;
+        self.hosts
+            .retain(|ip, _| !probed.contains(*ip) || answered.contains_key(ip));
+        for (ip, host) in answered {
+            let watched = self.hosts.entry(ip).or_insert_with(|| Watched {
+                host: host.clone(),
+                stable_rounds: 0,
+                next_round: 0,
+            });
+            if watched.host == host {
+                watched.stable_rounds += 1;
+            } else {
+                watched.host = host;
+                watched.stable_rounds = 0;
+            }
+            watched.next_round = round + backoff.interval(watched.stable_rounds);
+        }
+
+        self.round += 1;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt_packet::test_support::packet;
+    use std::time::Duration;
+
+    fn result(ip: Ipv4Addr, name: &[u8; 15]) -> ScanResult {
+        let packet = packet(ip, &[(name, 0x00, 0x0400)], [0; 6]);
+        ScanResult::new(packet, Duration::from_millis(1))
+    }
+
+    #[test]
+    fn reports_changes_and_backs_off_stable_hosts() {
+        let targets: TargetSet = "10.1.2.1-4".parse().unwrap();
+        let server = Ipv4Addr::new(10, 1, 2, 3);
+        let mut watcher = Watcher::with_backoff(Backoff {
+            rounds_per_step: 2,
+            max_steps: 2,
+        });
+
+        let mut probed = Vec::new();
+        for round in 0..12 {
+            let due = watcher.due(&targets);
+            probed.push(due.contains(server));
+            let results = if due.contains(server) {
+                vec![result(server, b"FILESERVER     ")]
+            } else {
+                Vec::new()
+            };
+            let changes = watcher.update(&due, &results);
+            assert!(changes.is_empty(), "round {} reported {:?}", round, changes);
+        }
+        // Every round at first, then every other round, then every fourth
+        let expected = [true, true, false, true, false, true, false, false, false, true, false, false];
+        assert_eq!(probed, expected);
+
+        // Round 12 isn't due, so the rename is only seen in round 13
+        let laptop = result(Ipv4Addr::new(10, 1, 2, 1), b"LAPTOP         ");
+        let due = watcher.due(&targets);
+        assert!(!due.contains(server));
+        let changes = watcher.update(&due, std::slice::from_ref(&laptop));
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], Change::Appeared { host } if host.name == "LAPTOP"));
+
+        let due = watcher.due(&targets);
+        assert!(due.contains(server));
+        let changes = watcher.update(&due, &[laptop, result(server, b"BACKUP         ")]);
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(&changes[0], Change::Renamed { new, .. } if new == "BACKUP"));
+    }
+}
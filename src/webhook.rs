@@ -0,0 +1,297 @@
+use crate::diff::Change;
+use crate::watch::Event;
+use std::error::Error;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_RETRY_DELAY_SECONDS: u64 = 2;
+const DEFAULT_BATCH_SIZE: usize = 50;
+/// The longest wait between retries, so a failing endpoint can't hold up the
+/// next round of a watch for long
+const MAX_RETRY_DELAY_SECONDS: u64 = 10;
+
+/// Posts the changes seen while watching to an HTTP endpoint.
+///
+/// Only new hosts and changed name or MAC bindings are posted. The changes
+/// from one round are sent together, at most `batch_size` to a request.
+///
+/// The payload is `{"events": [...]}` unless a template is given. In a
+/// template, `{events}` is replaced by the JSON array of changes, `{summary}`
+/// by the changes as lines of text (escaped to go inside a JSON string), and
+/// `{count}` by how many there are, e.g. `{"text": "{summary}"}`.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+    pub template: Option<String>,
+    /// How many times a request is retried after a connection error or a 5xx
+    /// response. Other responses aren't retried, as they won't change.
+    pub retries: u32,
+    /// How long to wait before the first retry, doubled for each one after up
+    /// to `MAX_RETRY_DELAY_SECONDS`
+    pub retry_delay: Duration,
+    pub batch_size: usize,
+}
+
+#[derive(Debug)]
+pub enum WebhookError {
+    RequestError(String, String),
+    /// Some of a round's batches couldn't be posted: how many batches there
+    /// were, and why each failed one did
+    BatchError(usize, Vec<WebhookError>),
+}
+
+impl Error for WebhookError {}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            WebhookError::RequestError(url, e) => write!(f, "Couldn't post to {}: {}", url, e),
+            WebhookError::BatchError(batches, errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{} of {} batches failed. {}", errors.len(), batches, errors.join(". "))
+            }
+        }
+    }
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Webhook {
+        Webhook {
+            url: String::from(url),
+            template: None,
+            retries: DEFAULT_RETRIES,
+            retry_delay: Duration::from_secs(DEFAULT_RETRY_DELAY_SECONDS),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Posts the events worth alerting on, returning how many were sent.
+    /// Every batch is tried even if an earlier one failed.
+    pub fn send(&self, events: &[Event]) -> Result<usize, WebhookError> {
+        let alerts: Vec<Event> = events
+            .iter()
+            .filter(|event| is_alert(&event.change))
+            .cloned()
+            .collect();
+        let batches: Vec<&[Event]> = alerts.chunks(self.batch_size.max(1)).collect();
+        let errors: Vec<WebhookError> = batches
+            .iter()
+            .filter_map(|batch| self.post(&self.payload(batch)).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(alerts.len())
+        } else {
+            Err(WebhookError::BatchError(batches.len(), errors))
+        }
+    }
+
+    pub fn payload(&self, events: &[Event]) -> String {
+        let json = serde_json::to_string(events).unwrap_or_else(|_| String::from("[]"));
+        match self.template {
+            Some(ref template) => {
+                let summary: Vec<String> = events.iter().map(|event| event.change.to_string()).collect();
+                // The summary as a JSON string, without the surrounding quotes
+                let summary = serde_json::to_string(&summary.join("\n")).unwrap_or_default();
+                let count = events.len().to_string();
+                substitute(
+                    template,
+                    &[
+                        ("{events}", &json),
+                        ("{summary}", &summary[1..summary.len() - 1]),
+                        ("{count}", &count),
+                    ],
+                )
+            }
+            None => format!("{{\"events\":{}}}", json),
+        }
+    }
+
+    fn post(&self, payload: &str) -> Result<(), WebhookError> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let response = ureq::post(&self.url)
+                .set("Content-Type", "application/json")
+                .send_string(payload);
+            match response {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.retries && is_retryable(&e) => {
+                    eprintln!("Posting to {} failed, retrying: {}", self.url, e);
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(Duration::from_secs(MAX_RETRY_DELAY_SECONDS));
+                    attempt += 1;
+                }
+                Err(e) => return Err(WebhookError::RequestError(self.url.clone(), e.to_string())),
+            }
+        }
+    }
+}
+
+/// Replaces the placeholders in one pass, so text that was substituted in,
+/// like a host named `{count}`, is left as it is
+fn substitute(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values.iter().find(|(placeholder, _)| rest.starts_with(placeholder)) {
+            Some((placeholder, value)) => {
+                filled.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Whether a failed request might succeed if tried again: it couldn't be
+/// made, or the server had a problem
+fn is_retryable(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Status(status, _) => *status >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+/// New hosts and hosts whose name or MAC changed, the changes that can mean
+/// a rogue machine
+fn is_alert(change: &Change) -> bool {
+    matches!(
+        change,
+        Change::Appeared { .. } | Change::Renamed { .. } | Change::Moved { .. } | Change::MacChanged { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::Host;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    fn host(name: &str) -> Host {
+        Host {
+            ip: Ipv4Addr::new(10, 1, 2, 3),
+            name: String::from(name),
+            workgroup: None,
+            mac: String::from("2C:41:38:BA:C3:64"),
+            names: Vec::new(),
+        }
+    }
+
+    fn appeared(name: &str) -> Event {
+        Event {
+            time: 1_776_600_000,
+            change: Change::Appeared { host: host(name) },
+        }
+    }
+
+    /// Answers the given statuses in turn, returning the bodies it received
+    fn listen(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.to_lowercase().starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                let response = format!("HTTP/1.1 {} OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn batches_alerts_and_retries_failed_posts() {
+        let (url, listener) = listen(vec![500, 200, 200]);
+        let mut webhook = Webhook::new(&url);
+        webhook.retry_delay = Duration::from_millis(10);
+        webhook.batch_size = 2;
+        webhook.template = Some(String::from("{\"text\": \"{summary}\", \"count\": {count}}"));
+
+        let vanished = Event {
+            time: 1_776_600_000,
+            change: Change::Vanished { host: host("OLDBOX") },
+        };
+        let events = vec![appeared("GUEST-1"), vanished, appeared("GUEST-2"), appeared("GUEST-3")];
+        assert_eq!(webhook.send(&events).unwrap(), 3);
+
+        let bodies = listener.join().unwrap();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], bodies[1]);
+        let first: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(first["count"], 2);
+        assert!(first["text"].as_str().unwrap().contains("GUEST-2 appeared"));
+        assert!(!bodies[1].contains("OLDBOX"));
+    }
+
+    #[test]
+    fn client_errors_arent_retried() {
+        let (url, listener) = listen(vec![404, 200]);
+        let mut webhook = Webhook::new(&url);
+        webhook.retry_delay = Duration::from_millis(10);
+
+        assert!(webhook.send(&[appeared("GUEST-1")]).is_err());
+        // The next request is a new send, not a retry of the first
+        assert_eq!(webhook.send(&[appeared("GUEST-2")]).unwrap(), 1);
+        let bodies = listener.join().unwrap();
+        assert!(bodies[1].contains("GUEST-2"));
+    }
+
+    #[test]
+    fn a_failed_batch_doesnt_stop_the_rest() {
+        let (url, listener) = listen(vec![500, 500, 200]);
+        let mut webhook = Webhook::new(&url);
+        webhook.retries = 1;
+        webhook.retry_delay = Duration::from_millis(10);
+        webhook.batch_size = 1;
+
+        let result = webhook.send(&[appeared("GUEST-1"), appeared("GUEST-2")]);
+        assert!(matches!(result, Err(WebhookError::BatchError(2, ref errors)) if errors.len() == 1));
+        let bodies = listener.join().unwrap();
+        assert!(bodies[2].contains("GUEST-2"));
+    }
+
+    #[test]
+    fn default_payload_lists_the_events() {
+        let payload = Webhook::new("http://localhost/").payload(&[appeared("GUEST-1")]);
+        let value: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(value["events"][0]["change"], "appeared");
+        assert_eq!(value["events"][0]["host"]["name"], "GUEST-1");
+    }
+
+    #[test]
+    fn substituted_text_isnt_substituted_again() {
+        let mut webhook = Webhook::new("http://localhost/");
+        webhook.template = Some(String::from("{\"text\": \"{summary}\", \"count\": {count}, \"raw\": \"{other}\"}"));
+        let payload = webhook.payload(&[appeared("{count}")]);
+        let value: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert!(value["text"].as_str().unwrap().contains("{count}"));
+        assert_eq!(value["count"], 1);
+        assert_eq!(value["raw"], "{other}");
+    }
+}